@@ -13,35 +13,226 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-#[macro_use]
-extern crate log;
 extern crate glean;
+use glean::GleanConfig;
+use glean::cmdline::KernelCmdline;
 use glean::config_drive::ConfigDrive;
-use glean::sys::SysInterfaces;
-use glean::live_networks::LiveNetworks;
+use glean::config_drive::meta_data::MetaData;
+use glean::model::{Kind, Method};
+use glean::instance::Task;
+use glean::state::State;
+use glean::settings::Settings;
+use glean::sys::{Filter, Rule, Strategy};
+use std::io::{self, Write};
+use std::process;
+use std::time::Duration;
 
 mod options;
 
+use options::Command;
+
+// getopts failures, from sysexits.h like the ones in glean::Error
+static EX_USAGE: i32 = 64;
+
 #[cfg(not(test))]
 fn main() {
 
-    let opts = options::Opt::new();
+    let opts = match options::Opt::new() {
+        Ok(opts) => opts,
+        Err(why) => {
+            let _ = writeln!(&mut io::stderr(), "glean: {}", why);
+            process::exit(EX_USAGE);
+        },
+    };
     if opts.help {
         println!("{}", opts.usage);
         return;
     }
+    match opts.output {
+        None => {},
+        Some(ref format) if format == "text" || format == "json" => {},
+        Some(ref format) => {
+            let _ = writeln!(&mut io::stderr(), "glean: unknown output format: {}", format);
+            process::exit(EX_USAGE);
+        },
+    }
 
-    let config_drive = ConfigDrive::new(&opts.root);
-    match config_drive {
-        Some(ref interface) => {
-            debug!("Network {:?}", interface.interface_map);
-            debug!("DNS {:?}", interface.dns);
+    let result = match opts.command {
+        Command::Status => status(&opts),
+        Command::Show => show(&opts),
+        _ => run(&opts),
+    };
+    match result {
+        Ok(()) => {},
+        Err(why) => {
+            let _ = writeln!(&mut io::stderr(), "glean: {}", why);
+            process::exit(why.exit_code());
         },
-        None => {}
+    }
+}
+
+fn run(opts: &options::Opt) -> glean::Result<()> {
+    // <root>/etc/glean/glean.conf, then glean.* and ip= from the kernel
+    // command line, then flags
+    let settings = try!(Settings::new(&opts.root));
+    let cmdline = try!(KernelCmdline::new(&opts.root));
+    let mut builder = cmdline.apply(settings.apply(GleanConfig::builder()));
+    if opts.noop {
+        builder = builder.noop(true);
+    }
+    if let Some(ref root) = opts.root {
+        builder = builder.root(root);
+    }
+    if let Some(ref distro) = opts.distro {
+        builder = builder.distro(distro);
+    }
+    if let Some(ref interface) = opts.interface {
+        builder = builder.interface(interface);
+    }
+    if let Some(seconds) = opts.carrier_timeout {
+        builder = builder.carrier_timeout(Duration::from_secs(seconds));
+    }
+    if let Some(ref name) = opts.carrier_strategy {
+        match Strategy::from_name(name) {
+            Some(strategy) => builder = builder.carrier_strategy(strategy),
+            None => {
+                let _ = writeln!(&mut io::stderr(), "glean: unknown carrier strategy: {}", name);
+                process::exit(EX_USAGE);
+            },
+        }
+    }
+    // Rules on the command line replace the ones from glean.conf
+    if !opts.include.is_empty() || !opts.exclude.is_empty() {
+        let mut filter = Filter::new();
+        filter.include = parse_rules(&opts.include);
+        filter.exclude = parse_rules(&opts.exclude);
+        builder = builder.filter(filter);
     }
 
-    let sys_interfaces = SysInterfaces::new(&opts.root, &opts.interface, opts.noop);
+    // A command only narrows down what glean.conf and the kernel command
+    // line allow, so glean.disable still stops glean hostname. --explain
+    // and --diff are reports on the network config alone.
+    let wanted = match opts.command {
+        _ if opts.explain || opts.diff => vec![Task::Network],
+        Command::Network | Command::Plan => vec![Task::Network],
+        Command::SshKeys => vec![Task::SshKeys],
+        Command::Hostname => vec![Task::Hostname],
+        _ => vec![Task::Network, Task::Hostname, Task::SshKeys],
+    };
+    let mut config = builder.build();
+    config.tasks.retain(|task| wanted.contains(task));
 
-    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, &opts.root);
-    println!("{:?}", live_networks.get_output());
+    let report = try!(glean::run(&config));
+    let network = match report.network {
+        Some(network) => network,
+        None => return Ok(()),
+    };
+    if opts.explain {
+        print!("{}", network.explanation);
+    } else if opts.diff {
+        print!("{}", network.files.diff());
+    } else {
+        match (opts.command, &opts.output) {
+            (_, &Some(ref format)) if format == "json" => println!("{}", network.plan.to_json()),
+            (Command::Plan, _) => for file in network.files.files().iter() {
+                println!("==> {} ({})", file.path.display(), file.action);
+                print!("{}", file.content);
+            },
+            // What was done to which file, without the contents plan shows
+            (Command::Default, _) => for file in network.files.files().iter() {
+                println!("==> {} ({})", file.path.display(), file.action);
+            },
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+fn parse_rules(rules: &Vec<String>) -> Vec<Rule> {
+    let mut parsed = Vec::new();
+    for rule in rules.iter() {
+        match Rule::from_str(rule) {
+            Some(rule) => parsed.push(rule),
+            None => {
+                let _ = writeln!(&mut io::stderr(), "glean: invalid interface rule: {}", rule);
+                process::exit(EX_USAGE);
+            },
+        }
+    }
+    return parsed;
+}
+
+// What glean made of the config drive, before any of it is matched up with
+// the interfaces that are here
+fn show(opts: &options::Opt) -> glean::Result<()> {
+    let config_drive = try!(ConfigDrive::new(&opts.root));
+    let meta_data = try!(MetaData::new(&opts.root));
+    if config_drive.is_none() && meta_data.is_none() {
+        return Err(glean::Error::MissingData(String::from("no config drive data")));
+    }
+    if let Some(ref meta_data) = meta_data {
+        println!("instance {}", meta_data.uuid);
+        if let Some(hostname) = meta_data.hostname.clone().or(meta_data.name.clone()) {
+            println!("    hostname: {}", hostname);
+        }
+        if let Some(ref keys) = meta_data.public_keys {
+            let mut names: Vec<&String> = keys.keys().collect();
+            names.sort();
+            for name in names {
+                println!("    ssh key: {}", name);
+            }
+        }
+    }
+    let network = match config_drive {
+        Some(drive) => drive.network,
+        None => {
+            println!("no network config");
+            return Ok(());
+        },
+    };
+    for device in network.devices.iter() {
+        match device.name.is_empty() {
+            true => println!("{}", device.id),
+            false => println!("{}", device.name),
+        }
+        match device.kind {
+            Kind::Ethernet => {},
+            Kind::Bond { ref slaves, ref mode } =>
+                println!("    bond: {} ({})", slaves.join(" "), mode.as_ref().map(|mode| &mode[..]).unwrap_or("default mode")),
+            Kind::Vlan { ref parent, vlan_id } => println!("    vlan: {} on {}", vlan_id, parent),
+        }
+        if let Some(ref address) = device.hardware_address {
+            println!("    hwaddr: {}", address);
+        }
+        if let Some(mtu) = device.mtu {
+            println!("    mtu: {}", mtu);
+        }
+        println!("    method: {}", match device.method {
+            Method::Static => "static",
+            Method::Dhcp => "dhcp",
+            Method::Manual => "manual",
+        });
+        for address in device.addresses.iter() {
+            println!("    address: {}", address);
+        }
+        for route in device.routes.iter() {
+            println!("    route: {} via {}", route.destination, route.gateway);
+        }
+        for dns in device.dns.iter() {
+            println!("    dns: {}", dns);
+        }
+    }
+    Ok(())
+}
+
+fn status(opts: &options::Opt) -> glean::Result<()> {
+    let state = match try!(State::load(&opts.root)) {
+        Some(state) => state,
+        None => return Err(glean::Error::MissingData(String::from("glean has not recorded a run"))),
+    };
+    match opts.output {
+        Some(ref format) if format == "json" => println!("{}", state.to_json()),
+        _ => print!("{}", state),
+    }
+    Ok(())
 }
@@ -16,47 +16,191 @@
 extern crate getopts;
 use self::getopts::Options;
 use std::env;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    // What glean did before it had subcommands, kept for existing units
+    Default,
+    Network,
+    SshKeys,
+    Hostname,
+    All,
+    Show,
+    Plan,
+    Status,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Command> {
+        match name {
+            "network" => Some(Command::Network),
+            "ssh-keys" => Some(Command::SshKeys),
+            "hostname" => Some(Command::Hostname),
+            "all" => Some(Command::All),
+            "show" => Some(Command::Show),
+            "plan" => Some(Command::Plan),
+            "status" => Some(Command::Status),
+            _ => None,
+        }
+    }
+
+    fn summary(&self) -> &'static str {
+        match *self {
+            Command::Default => "",
+            Command::Network => "Configure network interfaces",
+            Command::SshKeys => "Add ssh keys from meta data to root's authorized_keys",
+            Command::Hostname => "Set the hostname from meta data",
+            Command::All => "Configure interfaces, hostname and ssh keys",
+            Command::Show => "Print what glean makes of the config drive",
+            Command::Plan => "Print the files glean would write, changing nothing",
+            Command::Status => "Print what glean recorded about its last run",
+        }
+    }
+
+    // Which flags make sense for which commands
+    fn writes(&self) -> bool {
+        match *self {
+            Command::Default | Command::Network | Command::SshKeys | Command::Hostname | Command::All => true,
+            _ => false,
+        }
+    }
+
+    fn plans_network(&self) -> bool {
+        match *self {
+            Command::Default | Command::Network | Command::All | Command::Plan => true,
+            _ => false,
+        }
+    }
+
+    fn reports(&self) -> bool {
+        match *self {
+            Command::Default | Command::Plan => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Command::Default => write!(f, ""),
+            Command::Network => write!(f, "network"),
+            Command::SshKeys => write!(f, "ssh-keys"),
+            Command::Hostname => write!(f, "hostname"),
+            Command::All => write!(f, "all"),
+            Command::Show => write!(f, "show"),
+            Command::Plan => write!(f, "plan"),
+            Command::Status => write!(f, "status"),
+        }
+    }
+}
+
+static COMMANDS: [Command; 7] = [
+    Command::Network, Command::SshKeys, Command::Hostname, Command::All,
+    Command::Show, Command::Plan, Command::Status,
+];
 
 #[derive(Debug)]
 pub struct Opt {
+  pub command: Command,
   pub help: bool,
   pub noop: bool,
+  pub explain: bool,
+  pub diff: bool,
   pub root: Option<String>,
   pub distro: Option<String>,
   pub interface: Option<String>,
+  pub output: Option<String>,
+  pub carrier_timeout: Option<u64>,
+  pub carrier_strategy: Option<String>,
+  pub include: Vec<String>,
+  pub exclude: Vec<String>,
   pub usage: String,
 }
 
 impl Opt {
-    pub fn new() -> Opt {
+    pub fn new() -> Result<Opt, String> {
         let args: Vec<String> = env::args().collect();
         let program = args[0].clone();
 
-        let mut opts = Options::new();
+        // The subcommand comes first, anything else is the old flags only
+        let (command, args) = match args.get(1).map(|arg| Command::from_name(arg)) {
+            Some(Some(command)) => (command, &args[2..]),
+            Some(None) if !args[1].starts_with('-') => return Err(format!("unknown command: {}", args[1])),
+            _ => (Command::Default, &args[1..]),
+        };
 
+        let mut opts = Options::new();
         opts.optflag("h", "help", "print this help menu");
-        opts.optflag("n", "noop", "Do not write files");
         opts.optopt("", "root", "Mounted root for config drive info [default: '/mnt/config']", "ROOT");
-        opts.optopt("", "distro", "Override detected distro", "DISTRO");
-        opts.optopt("i", "interface", "Interface to process", "INTERFACE");
+        if command.writes() {
+            opts.optflag("n", "noop", "Do not write files");
+        }
+        if command.plans_network() {
+            opts.optopt("", "distro", "Override detected distro", "DISTRO");
+            opts.optopt("i", "interface", "Interface to process", "INTERFACE");
+            opts.optopt("", "carrier-timeout", "Seconds to wait for carrier on links brought up [default: 10]", "SECONDS");
+            opts.optopt("", "carrier-strategy", "How to wait for carrier: events, poll or none [default: events]", "STRATEGY");
+            opts.optmulti("", "include", "Only configure interfaces matching a rule, e.g. driver:virtio_net", "RULE");
+            opts.optmulti("", "exclude", "Never configure interfaces matching a rule, e.g. name:docker* or bus:usb", "RULE");
+        }
+        if command.reports() {
+            opts.optflag("", "explain", "Report what was done with each interface and why, implies --noop");
+            opts.optflag("", "diff", "Show a unified diff of each file against what is on disk, implies --noop");
+        }
+        if command.reports() || command == Command::Status {
+            opts.optopt("", "output", "Output format, text or json [default: text]", "FORMAT");
+        }
 
-        let matches = match opts.parse(&args[1..]) {
-            Ok(m) => { m }
-            Err(f) => { panic!(f.to_string()) }
+        let matches = try!(opts.parse(args).map_err(|why| why.to_string()));
+        if let Some(extra) = matches.free.get(0) {
+            return Err(format!("unexpected argument: {}", extra));
+        }
+        let brief = match command {
+            Command::Default => {
+                let mut brief = format!("Usage: {} [COMMAND] [options]\n\nCommands:", program);
+                for command in COMMANDS.iter() {
+                    brief.push_str(&format!("\n    {:<10}{}", command.to_string(), command.summary()));
+                }
+                brief.push_str(&format!("\n\nWithout a command, glean does all of them. See {} COMMAND --help.", program));
+                brief
+            },
+            command => format!("Usage: {} {} [options]\n\n{}.", program, command, command.summary()),
+        };
+        // getopts panics when asked about an option it wasn't given, and
+        // not every command has every option
+        let network = command.plans_network();
+        let reports = command.reports();
+        let explain = reports && matches.opt_present("explain");
+        let diff = reports && matches.opt_present("diff");
+        let carrier_timeout = match network {
+            true => match matches.opt_str("carrier-timeout") {
+                Some(value) => match value.parse() {
+                    Ok(seconds) => Some(seconds),
+                    Err(_) => return Err(format!("--carrier-timeout {} is not a number of seconds", value)),
+                },
+                None => None,
+            },
+            false => None,
         };
-        let brief = format!("Usage: {} [options]", program);
         let ret = Opt {
+            command: command,
             usage: opts.usage(&brief),
             help: matches.opt_present("help"),
-            noop: matches.opt_present("noop"),
-            root: match matches.opt_present("root") {
-                true => matches.opt_str("root"),
-                false => None,
-            },
-            distro: matches.opt_str("distro"),
-            interface: matches.opt_str("interface"),
+            noop: command == Command::Plan || (command.writes() && matches.opt_present("noop")) || explain || diff,
+            explain: explain,
+            diff: diff,
+            root: matches.opt_str("root"),
+            distro: if network { matches.opt_str("distro") } else { None },
+            interface: if network { matches.opt_str("interface") } else { None },
+            output: if reports || command == Command::Status { matches.opt_str("output") } else { None },
+            carrier_timeout: carrier_timeout,
+            carrier_strategy: if network { matches.opt_str("carrier-strategy") } else { None },
+            include: if network { matches.opt_strs("include") } else { Vec::new() },
+            exclude: if network { matches.opt_strs("exclude") } else { Vec::new() },
         };
 
-        return ret;
+        return Ok(ret);
     }
 }
@@ -0,0 +1,301 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Changing what glean does from the boot loader, for rescue and debugging,
+// without rebuilding the image. Read from <root>/proc/cmdline:
+//
+//   glean.disable              do nothing at all
+//   glean.noop                 work everything out but change nothing
+//   glean.distro=NAME          as --distro
+//   glean.interfaces=A,B       only configure these interfaces
+//   glean.dhcp                 ignore config drive and overrides.json
+//   ip=...                     dracut style interface config, see below
+//   nameserver=ADDR            nameservers for static ip= interfaces
+//
+// The dracut forms of ip= understood are
+//
+//   ip=dhcp                    same as glean.dhcp
+//   ip=IFACE:dhcp[:MTU[:MAC]]
+//   ip=ADDR:[PEER]:GW:NETMASK:HOSTNAME:IFACE:none[:MTU[:MAC]]
+//   ip=ADDR:[PEER]:GW:NETMASK:HOSTNAME:IFACE:none[:DNS1[:DNS2]]
+//
+// with IPv6 addresses in brackets, and on, any, dhcp6 and auto6 all taken
+// as dhcp. Each ip= becomes a device that wins over overrides.json and
+// config drive. These take precedence over glean.conf.
+//
+// dracut reads ip= too and knows forms glean doesn't, so ones glean can't
+// use are skipped with a warning rather than stopping the boot.
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use ::{Error, Result};
+use ::ip::Prefix;
+use ::model::{Device, Kind, Method, Route};
+use ::pipeline::GleanConfigBuilder;
+use ::sys::{HardwareAddress, Rule};
+
+#[derive(Debug, Clone, Default)]
+pub struct KernelCmdline {
+    pub disable: bool,
+    pub noop: bool,
+    pub distro: Option<String>,
+    pub interfaces: Option<Vec<String>>,
+    pub dhcp: bool,
+    pub devices: Vec<Device>,
+}
+
+impl KernelCmdline {
+    // No proc/cmdline under the root, as in most image builds, is the same
+    // as an empty one
+    pub fn new(root: &Option<String>) -> Result<KernelCmdline> {
+        let base_root_path = match root {
+            &Some(ref path) => PathBuf::from(path),
+            &None => PathBuf::from("/"),
+        };
+        let path = base_root_path.join("proc/cmdline");
+        let mut content = String::new();
+        match File::open(&path).and_then(|mut file| file.read_to_string(&mut content)) {
+            Ok(_) => Ok(KernelCmdline::parse(&content)),
+            Err(ref why) if why.kind() == ::std::io::ErrorKind::NotFound => Ok(KernelCmdline::default()),
+            Err(why) => Err(Error::Io(path, why)),
+        }
+    }
+
+    pub fn parse(cmdline: &str) -> KernelCmdline {
+        let mut cmdline_config = KernelCmdline::default();
+        let mut nameservers = Vec::new();
+        for param in split_params(cmdline) {
+            let (key, value) = match param.find('=') {
+                Some(index) => (&param[..index], Some(&param[index + 1..])),
+                None => (&param[..], None),
+            };
+            match (key, value) {
+                ("glean.disable", value) => cmdline_config.disable = is_set(value),
+                ("glean.noop", value) => cmdline_config.noop = is_set(value),
+                ("glean.dhcp", value) => cmdline_config.dhcp = is_set(value),
+                ("glean.distro", Some(distro)) => cmdline_config.distro = Some(String::from(distro)),
+                ("glean.interfaces", Some(names)) => cmdline_config.interfaces = Some(
+                    names.split(',').filter(|name| !name.is_empty()).map(String::from).collect()),
+                ("ip", Some(value)) => match parse_ip(value) {
+                    Ok(Some(device)) => cmdline_config.devices.push(device),
+                    Ok(None) => cmdline_config.dhcp = true,
+                    Err(why) => warn!("Ignoring ip={}: {}", value, why),
+                },
+                ("nameserver", Some(value)) => match unbracket(value).parse::<IpAddr>() {
+                    Ok(address) => nameservers.push(address),
+                    Err(_) => warn!("Ignoring nameserver={}: not an IP address", value),
+                },
+                (key, _) if key.starts_with("glean.") => warn!("Ignoring unknown kernel parameter {}", param),
+                _ => {},
+            }
+        }
+        for device in cmdline_config.devices.iter_mut() {
+            if device.method == Method::Static && device.dns.is_empty() {
+                device.dns = nameservers.clone();
+            }
+        }
+        cmdline_config
+    }
+
+    // On top of glean.conf, under command line flags
+    pub fn apply(&self, mut builder: GleanConfigBuilder) -> GleanConfigBuilder {
+        if self.disable {
+            builder = builder.tasks(&[]);
+        }
+        if self.noop {
+            builder = builder.noop(true);
+        }
+        if let Some(ref distro) = self.distro {
+            builder = builder.distro(distro);
+        }
+        if let Some(ref interfaces) = self.interfaces {
+            let rules: Vec<Rule> = interfaces.iter()
+                .filter_map(|name| Rule::from_str(&format!("name:{}", name)))
+                .collect();
+            builder = builder.include_only(rules);
+        }
+        if self.dhcp {
+            builder = builder.dhcp(true);
+        }
+        for device in self.devices.iter() {
+            builder = builder.device(device.clone());
+        }
+        builder
+    }
+}
+
+// Whitespace separated, except inside double quotes, as the kernel does it
+fn split_params(cmdline: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in cmdline.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    params.push(current);
+                    current = String::new();
+                }
+            },
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        params.push(current);
+    }
+    params
+}
+
+// A bare glean.noop is as good as glean.noop=1
+fn is_set(value: Option<&str>) -> bool {
+    match value {
+        None => true,
+        Some(value) => match &value.to_lowercase()[..] {
+            "0" | "no" | "false" | "off" => false,
+            _ => true,
+        },
+    }
+}
+
+fn unbracket(value: &str) -> &str {
+    value.trim_matches(|c| c == '[' || c == ']')
+}
+
+// ip= fields split on colons, except inside the brackets around IPv6
+// addresses
+fn split_fields(value: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut bracketed = false;
+    for c in value.chars() {
+        match c {
+            '[' => bracketed = true,
+            ']' => bracketed = false,
+            ':' if !bracketed => {
+                fields.push(current);
+                current = String::new();
+                continue;
+            },
+            _ => {},
+        }
+        if c != '[' && c != ']' {
+            current.push(c);
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+fn is_dhcp(autoconf: &str) -> bool {
+    match autoconf {
+        "dhcp" | "on" | "any" | "dhcp6" | "auto6" | "either6" => true,
+        _ => false,
+    }
+}
+
+// A device for an ip= parameter, or None for one that asks for dhcp
+// everywhere
+fn parse_ip(value: &str) -> ::std::result::Result<Option<Device>, String> {
+    let fields = split_fields(value);
+    if fields.len() == 1 {
+        return match is_dhcp(&fields[0]) {
+            true => Ok(None),
+            false => Err(format!("unknown autoconf '{}'", fields[0])),
+        };
+    }
+    // IFACE:dhcp, or the full ADDR:PEER:GW:NETMASK:HOSTNAME:IFACE:AUTOCONF
+    let (name, autoconf, address, gateway, rest) = if fields.len() < 7 || fields[0].parse::<IpAddr>().is_err() {
+        (fields[0].clone(), fields[1].clone(), None, None, &fields[2..])
+    } else {
+        let address: IpAddr = try!(fields[0].parse().map_err(|_| format!("'{}' is not an IP address", fields[0])));
+        let prefix = match Prefix::from_netmask(address, &fields[3]) {
+            Some(prefix) => prefix,
+            None => return Err(format!("'{}' is not a netmask for {}", fields[3], address)),
+        };
+        let gateway = match &fields[2][..] {
+            "" => None,
+            gateway => Some(try!(gateway.parse::<IpAddr>().map_err(|_| format!("'{}' is not an IP address", gateway)))),
+        };
+        match gateway {
+            Some(gateway) if gateway.is_ipv6() != prefix.is_ipv6() =>
+                return Err(format!("gateway {} is of the other address family", gateway)),
+            _ => {},
+        }
+        (fields[5].clone(), fields[6].clone(), Some(prefix), gateway, &fields[7..])
+    };
+    let method = match (&autoconf[..], address.is_some()) {
+        ("none", true) | ("off", true) | ("", true) => Method::Static,
+        (autoconf, _) if is_dhcp(autoconf) => Method::Dhcp,
+        (autoconf, _) => return Err(format!("unsupported autoconf '{}'", autoconf)),
+    };
+
+    // Then either MTU and MAC, or up to two nameservers
+    let mut mtu = None;
+    let mut hardware_address = None;
+    let mut dns = Vec::new();
+    match rest.first().map(|field| field.parse::<IpAddr>()) {
+        Some(Ok(_)) => for field in rest.iter().filter(|field| !field.is_empty()) {
+            dns.push(try!(field.parse().map_err(|_| format!("'{}' is not an IP address", field))));
+        },
+        Some(Err(_)) => {
+            if !rest[0].is_empty() {
+                mtu = Some(try!(rest[0].parse::<u16>().map_err(|_| format!("'{}' is not an MTU", rest[0]))));
+            }
+            let mac = rest[1..].join(":");
+            if !mac.is_empty() {
+                hardware_address = match HardwareAddress::parse(&mac) {
+                    Some(mac) => Some(mac),
+                    None => return Err(format!("'{}' is not a hardware address", mac)),
+                };
+            }
+        },
+        None => {},
+    }
+    if name.is_empty() && hardware_address.is_none() {
+        return Err(String::from("no interface or hardware address to apply it to"));
+    }
+
+    let routes = match (address, gateway) {
+        (Some(address), Some(gateway)) => vec![Route {
+            destination: Prefix::new(match address.address() {
+                IpAddr::V4(_) => "0.0.0.0".parse().unwrap(),
+                IpAddr::V6(_) => "::".parse().unwrap(),
+            }, 0).unwrap(),
+            gateway: gateway,
+        }],
+        _ => Vec::new(),
+    };
+    let id = match hardware_address {
+        Some(ref mac) if name.is_empty() => format!("ip={}", mac),
+        _ => format!("ip={}", name),
+    };
+    Ok(Some(Device {
+        id: id,
+        name: name,
+        networks: Vec::new(),
+        hardware_address: hardware_address,
+        kind: Kind::Ethernet,
+        mtu: mtu,
+        method: method,
+        addresses: address.into_iter().collect(),
+        routes: routes,
+        dns: dns,
+        master: None,
+    }))
+}
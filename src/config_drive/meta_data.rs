@@ -0,0 +1,49 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rustc_serialize::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use ::{Error, Result};
+use super::read_string_from_file;
+
+#[derive(RustcDecodable, Debug, Clone)]
+pub struct MetaData {
+    pub uuid: String,
+    pub hostname: Option<String>,
+    pub name: Option<String>,
+    pub public_keys: Option<HashMap<String, String>>,
+}
+
+impl MetaData {
+    pub fn new(root: &Option<String>) -> Result<Option<MetaData>> {
+        let base_root_path = match root {
+            &Some(ref path) => PathBuf::from(path),
+            &None => PathBuf::from("/"),
+        };
+        let path = base_root_path.join("mnt/config/openstack/latest/meta_data.json");
+        debug!("Trying Path: {}", path.display());
+        let json_string = match read_string_from_file(&path) {
+            Ok(string) => string,
+            Err(ref why) if why.is_not_found() => return Ok(None),
+            Err(why) => return Err(why),
+        };
+        match json::decode::<MetaData>(&json_string) {
+            Ok(data) => Ok(Some(data)),
+            Err(why) => Err(Error::Decode(path, why.to_string())),
+        }
+    }
+}
@@ -13,19 +13,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashMap;
+use std::fs::File;
+use std::io::prelude::*;
 use std::path::PathBuf;
 
+use ::{Error, Result};
+use ::model::Config;
+
+pub mod meta_data;
 pub mod network_info;
 
 #[derive(Debug, Clone)]
 pub struct ConfigDrive {
-    pub interface_map: HashMap<String, network_info::Network>,
-    pub dns: Vec<String>,
+    pub network: Config,
 }
 
 impl ConfigDrive {
-    pub fn new(root: &Option<String>) -> Option<ConfigDrive> {
+    // No config drive at all is not an error, we just fall back to DHCP.
+    // A config drive we can't read or make sense of is.
+    pub fn new(root: &Option<String>) -> Result<Option<ConfigDrive>> {
         let paths = vec![
             "openstack/latest/network_info.json",
             "openstack/latest/vendor_data.json",
@@ -34,13 +40,35 @@ impl ConfigDrive {
             let data_path = PathBuf::from(path);
             debug!("Trying Path: {}", path);
             match network_info::NetworkInfo::new(root, &data_path) {
-                Some(info) => return Some(ConfigDrive{
-                    interface_map: info.get_interface_map(),
-                    dns: info.get_dns()
-                }),
-                None => {},
+                Ok(info) => return Ok(Some(ConfigDrive{
+                    network: info.to_config(),
+                })),
+                Err(ref why) if why.is_not_found() => {},
+                Err(Error::MissingData(why)) => debug!("{}", why),
+                Err(why) => return Err(why),
             };
         }
-        return None;
+        return Ok(None);
     }
 }
+
+fn read_string_from_file(path: &PathBuf) -> Result<String> {
+    // Needs to be mutable because reading from it involves change
+    let mut file = match File::open(&path) {
+        Err(why) => {
+            debug!("couldn't open {}: {}", path.display(), why);
+            return Err(Error::Io(path.clone(), why));
+        },
+        Ok(file) => file,
+    };
+
+    let mut s = String::new();
+    match file.read_to_string(&mut s) {
+        Err(why) => {
+            debug!("couldn't read {}: {}", path.display(), why);
+            return Err(Error::Io(path.clone(), why));
+        },
+        Ok(ret) => ret,
+    };
+    return Ok(s);
+}
@@ -14,11 +14,14 @@
 // limitations under the License.
 
 use rustc_serialize::{json, Decodable, Decoder};
-use std::collections::HashMap;
-use std::error::Error;
-use std::fs::File;
-use std::io::prelude::*;
-use std::path::{PathBuf, Display};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use ::Error;
+use ::ip::Prefix;
+use ::model::{self, Config, Device, Kind, Method};
+use ::sys::HardwareAddress;
+use super::read_string_from_file;
 
 
 // Automatically generate `Decodable` trait implementations
@@ -28,7 +31,7 @@ use std::path::{PathBuf, Display};
 #[derive(Debug)]
 pub struct Service {
     pub service_type: String,
-    pub address: String,
+    pub address: IpAddr,
 }
 
 impl Decodable for Service {
@@ -36,28 +39,44 @@ impl Decodable for Service {
     decoder.read_struct("root", 0, |decoder| {
          Ok(Service{
           service_type: try!(decoder.read_struct_field("type", 0, |decoder| Decodable::decode(decoder))),
-          address: try!(decoder.read_struct_field("address", 0, |decoder| Decodable::decode(decoder)))
+          address: try!(decode_ip(decoder, "address")),
         })
     })
   }
 }
 
 
-#[derive(RustcDecodable, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub struct Route {
-    pub netmask: String,
-    pub network: String,
-    pub gateway: String,
+    pub destination: Prefix,
+    pub gateway: IpAddr,
+}
+
+impl Decodable for Route {
+  fn decode<D: Decoder>(decoder: &mut D) -> Result<Route, D::Error> {
+    decoder.read_struct("root", 0, |decoder| {
+        let network = try!(decode_ip(decoder, "network"));
+        let destination = try!(decode_prefix(decoder, network, "netmask"));
+        let gateway = try!(decode_ip(decoder, "gateway"));
+        match (destination.address(), gateway) {
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => {},
+            _ => return Err(decoder.error(&format!("route to {} has gateway {} of the other address family", destination, gateway))),
+        }
+        Ok(Route{
+          destination: destination,
+          gateway: gateway,
+        })
+    })
+  }
 }
 
 #[derive(Debug, Clone)]
 pub struct Network {
     network_id: String,
     pub network_type: String,
-    pub netmask: String,
+    pub address: Prefix,
     pub link: String,
     pub routes: Vec<Route>,
-    pub ip_address: String,
     pub id: String,
 }
 
@@ -66,25 +85,79 @@ pub struct Network {
 impl Decodable for Network {
   fn decode<D: Decoder>(decoder: &mut D) -> Result<Network, D::Error> {
     decoder.read_struct("root", 0, |decoder| {
+         let ip_address = try!(decode_ip(decoder, "ip_address"));
          Ok(Network{
           network_id: try!(decoder.read_struct_field("network_id", 0, |decoder| Decodable::decode(decoder))),
           network_type: try!(decoder.read_struct_field("type", 0, |decoder| Decodable::decode(decoder))),
-          netmask: try!(decoder.read_struct_field("netmask", 0, |decoder| Decodable::decode(decoder))),
+          address: try!(decode_prefix(decoder, ip_address, "netmask")),
           link: try!(decoder.read_struct_field("link", 0, |decoder| Decodable::decode(decoder))),
           routes: try!(decoder.read_struct_field("routes", 0, |decoder| Decodable::decode(decoder))),
-          ip_address: try!(decoder.read_struct_field("ip_address", 0, |decoder| Decodable::decode(decoder))),
           id: try!(decoder.read_struct_field("id", 0, |decoder| Decodable::decode(decoder))),
         })
     })
   }
 }
 
-#[derive(RustcDecodable, Debug)]
+fn decode_ip<D: Decoder>(decoder: &mut D, field: &str) -> Result<IpAddr, D::Error> {
+    let value: String = try!(decoder.read_struct_field(field, 0, |decoder| Decodable::decode(decoder)));
+    match value.parse() {
+        Ok(address) => Ok(address),
+        Err(_) => Err(decoder.error(&format!("{} '{}' is not an IP address", field, value))),
+    }
+}
+
+fn decode_prefix<D: Decoder>(decoder: &mut D, address: IpAddr, field: &str) -> Result<Prefix, D::Error> {
+    let value: String = try!(decoder.read_struct_field(field, 0, |decoder| Decodable::decode(decoder)));
+    match Prefix::from_netmask(address, &value) {
+        Some(prefix) => Ok(prefix),
+        None => Err(decoder.error(&format!("{} '{}' is not a valid netmask for {}", field, value, address))),
+    }
+}
+
+#[derive(Debug)]
 pub struct Link {
-    ethernet_mac_address: String,
-    mtu: u16,
+    ethernet_mac_address: Option<String>,
+    name: Option<String>,
+    link_type: Option<String>,
+    mtu: Option<u16>,
     id: String,
-    vif_id: String,
+    vif_id: Option<String>,
+    bond_links: Option<Vec<String>>,
+    bond_mode: Option<String>,
+    vlan_link: Option<String>,
+    vlan_id: Option<u16>,
+}
+
+// Custom class because type can't be a struct member
+impl Decodable for Link {
+  fn decode<D: Decoder>(decoder: &mut D) -> Result<Link, D::Error> {
+    decoder.read_struct("root", 0, |decoder| {
+         Ok(Link{
+          ethernet_mac_address: try!(decoder.read_struct_field("ethernet_mac_address", 0, |decoder| Decodable::decode(decoder))),
+          name: try!(decoder.read_struct_field("name", 0, |decoder| Decodable::decode(decoder))),
+          link_type: try!(decoder.read_struct_field("type", 0, |decoder| Decodable::decode(decoder))),
+          mtu: try!(decoder.read_struct_field("mtu", 0, |decoder| Decodable::decode(decoder))),
+          id: try!(decoder.read_struct_field("id", 0, |decoder| Decodable::decode(decoder))),
+          vif_id: try!(decoder.read_struct_field("vif_id", 0, |decoder| Decodable::decode(decoder))),
+          bond_links: try!(decoder.read_struct_field("bond_links", 0, |decoder| Decodable::decode(decoder))),
+          bond_mode: try!(decoder.read_struct_field("bond_mode", 0, |decoder| Decodable::decode(decoder))),
+          vlan_link: try!(decoder.read_struct_field("vlan_link", 0, |decoder| Decodable::decode(decoder))),
+          vlan_id: try!(decoder.read_struct_field("vlan_id", 0, |decoder| Decodable::decode(decoder))),
+        })
+    })
+  }
+}
+
+impl Link {
+    // Nova doesn't name links, but overrides can. An unnamed vlan is named
+    // after its parent once that is known.
+    fn device_name(&self) -> String {
+        match (&self.name, &self.link_type) {
+            (&Some(ref name), _) => name.clone(),
+            (&None, &Some(ref link_type)) if link_type == "vlan" => String::new(),
+            (&None, _) => self.id.clone(),
+        }
+    }
 }
 
 #[derive(RustcDecodable, Debug)]
@@ -96,89 +169,124 @@ pub struct NetworkInfo {
 
 #[derive(RustcDecodable, Debug)]
 pub struct VendorData {
-    network_info: NetworkInfo,
+    network_info: Option<NetworkInfo>,
 }
 
 impl NetworkInfo {
-    pub fn new(root: &Option<String>, data_path: &PathBuf) -> Option<NetworkInfo> {
+    pub fn new(root: &Option<String>, data_path: &PathBuf) -> ::Result<NetworkInfo> {
         let base_root_path = match root {
             &Some(ref path) => PathBuf::from(path),
             &None => PathBuf::from("/"),
         };
         let root_path = base_root_path.join("mnt/config").join(data_path);
-        let display = root_path.display();
-
-        let json_string = read_string_from_file(&root_path, &display);
-        match json_string {
-            Some(string) => return network_info_from_string(&string, &display),
-            None => return None,
-        }
+        return NetworkInfo::from_path(&root_path);
     }
 
-    pub fn get_interface_map(&self) -> HashMap<String, Network> {
+    pub fn from_path(path: &PathBuf) -> ::Result<NetworkInfo> {
+        let json_string = try!(read_string_from_file(&path));
+        return network_info_from_string(&json_string, &path);
+    }
 
-        let mut interfaces = HashMap::new();
+    // Every link becomes a device, with the networks attached to it as its
+    // addresses and routes
+    pub fn to_config(&self) -> Config {
+        let dns = self.get_dns();
+        let mut devices = Vec::new();
         for link in self.links.iter() {
-            for net in self.networks.iter() {
-                if net.link == link.id {
-                    let lower_mac = link.ethernet_mac_address.chars().flat_map(char::to_lowercase).collect::<String>();
-                    interfaces.insert(lower_mac, net.clone());
-                }
-            }
+            let hardware_address = match link.ethernet_mac_address {
+                Some(ref mac) => match HardwareAddress::parse(mac) {
+                    Some(mac) => Some(mac),
+                    None => {
+                        warn!("Ignoring unusable address {} of link {}", mac, link.id);
+                        None
+                    },
+                },
+                None => None,
+            };
+            let kind = match link.link_type {
+                Some(ref link_type) if link_type == "bond" => Kind::Bond {
+                    slaves: link.bond_links.clone().unwrap_or(Vec::new()),
+                    mode: link.bond_mode.clone(),
+                },
+                Some(ref link_type) if link_type == "vlan" => match (&link.vlan_link, link.vlan_id) {
+                    (&Some(ref parent), Some(vlan_id)) => Kind::Vlan { parent: parent.clone(), vlan_id: vlan_id },
+                    _ => {
+                        warn!("Ignoring vlan link {} without vlan_link and vlan_id", link.id);
+                        continue;
+                    },
+                },
+                _ => Kind::Ethernet,
+            };
+            let master = self.links.iter()
+                .find(|bond| match bond.bond_links {
+                    Some(ref slaves) => slaves.contains(&link.id),
+                    None => false,
+                })
+                .map(|bond| bond.device_name());
+            let networks: Vec<&Network> = self.networks.iter().filter(|net| net.link == link.id).collect();
+            let carries_vlan = self.links.iter().any(|vlan| vlan.vlan_link.as_ref() == Some(&link.id));
+            let method = match (networks.is_empty(), master.is_some() || carries_vlan) {
+                (false, _) => Method::Static,
+                (true, true) => Method::Manual,
+                (true, false) => Method::Dhcp,
+            };
+            devices.push(Device {
+                id: link.id.clone(),
+                name: link.device_name(),
+                networks: networks.iter().map(|net| net.id.clone()).collect(),
+                hardware_address: hardware_address,
+                kind: kind,
+                mtu: link.mtu,
+                addresses: networks.iter().map(|net| net.address).collect(),
+                routes: networks.iter().flat_map(|net| net.routes.iter()).map(|route| model::Route {
+                    destination: route.destination,
+                    gateway: route.gateway,
+                }).collect(),
+                dns: match method {
+                    Method::Static => dns.clone(),
+                    _ => Vec::new(),
+                },
+                method: method,
+                master: master,
+            });
         }
-        return interfaces;
+        Config { devices: devices, dns: dns }
     }
 
-    pub fn get_dns(&self) -> Vec<String> {
+    pub fn get_dns(&self) -> Vec<IpAddr> {
         let mut dns = Vec::new();
         for service in self.services.iter() {
             if service.service_type == "dns" {
-                dns.push(service.address.clone());
+                dns.push(service.address);
             }
         }
         return dns;
     }
 }
 
-fn read_string_from_file(root_path: &PathBuf, display: &Display) -> Option<String> {
-    // Needs to be mutable because reading from it involves change
-    let mut file = match File::open(&root_path) {
-        Err(why) => {
-            debug!("couldn't open {}: {}", display,
-                   Error::description(&why));
-            return None;
-        },
-        Ok(file) => file,
-    };
-
-    let mut s = String::new();
-    match file.read_to_string(&mut s) {
-        Err(why) => {
-            debug!("couldn't read {}: {}", display,
-                   Error::description(&why));
-            return None;
-        },
-        Ok(ret) => ret,
-    };
-    return Some(s);
-}
-
-fn network_info_from_string(json_string: &String, display: &Display) -> Option<NetworkInfo> {
+fn network_info_from_string(json_string: &String, path: &PathBuf) -> ::Result<NetworkInfo> {
     // First, try decoding as NetworkInfo
-    match json::decode::<NetworkInfo>(&json_string) {
+    let network_info_error = match json::decode::<NetworkInfo>(&json_string) {
         Err(why) => {
             debug!("Could not decode as NetworkInfo {}: {}",
-                   display, Error::description(&why));
+                   path.display(), why);
+            why
         },
-        Ok(data) => return Some(data),
+        Ok(data) => return Ok(data),
     };
     // Then as VendorData
     match json::decode::<VendorData>(&json_string) {
         Err(why) => {
             debug!("Could not decode as VendorData {}: {}",
-                   display, Error::description(&why));
-            return None;
+                   path.display(), why);
+            return Err(Error::Decode(path.clone(), network_info_error.to_string()));
+        },
+        Ok(VendorData { network_info: Some(data) }) => return Ok(data),
+        // A network_info.json with a bad address in it isn't vendor data
+        // either, but is worth saying what was wrong with it
+        Ok(VendorData { network_info: None }) => match network_info_error {
+            json::DecoderError::ApplicationError(why) => return Err(Error::Decode(path.clone(), why)),
+            _ => return Err(Error::MissingData(format!("no network_info in {}", path.display()))),
         },
-        Ok(data) => return Some(data.network_info),
     };
 }
@@ -0,0 +1,166 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Just enough of a line based unified diff to show what glean would change.
+// Config files are a handful of lines, so a plain LCS table is fine.
+
+use std::cmp;
+
+static CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+fn edit_script<'a>(old: &Vec<&'a str>, new: &Vec<&'a str>) -> Vec<(Op, &'a str)> {
+    // lcs[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            ops.push((Op::Equal, old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            ops.push((Op::Delete, old[i]));
+            i += 1;
+        } else {
+            ops.push((Op::Insert, new[j]));
+            j += 1;
+        }
+    }
+    for line in old[i..].iter() {
+        ops.push((Op::Delete, *line));
+    }
+    for line in new[j..].iter() {
+        ops.push((Op::Insert, *line));
+    }
+    return ops;
+}
+
+fn range(start: usize, count: usize) -> String {
+    // An empty range is shown starting at the line before it
+    if count == 0 {
+        return format!("{},0", start);
+    }
+    return format!("{},{}", start + 1, count);
+}
+
+// Returns an empty string when there is no difference
+pub fn unified(old_label: &str, new_label: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let ops = edit_script(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|&i| ops[i].0 != Op::Equal).collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Line numbers in old and new before each op
+    let mut positions = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for &(op, _) in ops.iter() {
+        positions.push((old_pos, new_pos));
+        match op {
+            Op::Equal => { old_pos += 1; new_pos += 1; },
+            Op::Delete => old_pos += 1,
+            Op::Insert => new_pos += 1,
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let mut c = 0;
+    while c < changes.len() {
+        let start = changes[c].saturating_sub(CONTEXT);
+        let mut last = changes[c];
+        while c + 1 < changes.len() && changes[c + 1] - last <= 2 * CONTEXT {
+            c += 1;
+            last = changes[c];
+        }
+        let end = cmp::min(last + 1 + CONTEXT, ops.len());
+
+        let hunk = &ops[start..end];
+        let old_count = hunk.iter().filter(|&&(op, _)| op != Op::Insert).count();
+        let new_count = hunk.iter().filter(|&&(op, _)| op != Op::Delete).count();
+        let (old_start, new_start) = positions[start];
+        out.push_str(&format!("@@ -{} +{} @@\n",
+                              range(old_start, old_count),
+                              range(new_start, new_count)));
+        for &(op, line) in hunk.iter() {
+            let prefix = match op {
+                Op::Equal => ' ',
+                Op::Delete => '-',
+                Op::Insert => '+',
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+        c += 1;
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unified;
+
+    #[test]
+    fn identical_files_have_no_diff() {
+        assert_eq!(unified("a", "b", "one\ntwo\n", "one\ntwo\n"), "");
+        assert_eq!(unified("a", "b", "", ""), "");
+    }
+
+    #[test]
+    fn new_files_are_all_insertions() {
+        assert_eq!(unified("/dev/null", "ifcfg-eth0", "", "DEVICE=eth0\nONBOOT=yes\n"),
+                   "--- /dev/null\n+++ ifcfg-eth0\n@@ -0,0 +1,2 @@\n+DEVICE=eth0\n+ONBOOT=yes\n");
+    }
+
+    #[test]
+    fn removed_files_are_all_deletions() {
+        assert_eq!(unified("ifcfg-eth9", "/dev/null", "DEVICE=eth9\nONBOOT=yes\n", ""),
+                   "--- ifcfg-eth9\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-DEVICE=eth9\n-ONBOOT=yes\n");
+    }
+
+    #[test]
+    fn changes_are_shown_with_context() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n";
+        assert_eq!(unified("old", "new", old, new),
+                   "--- old\n+++ new\n@@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n");
+
+        // Changes further apart than twice the context get a hunk each
+        let new = "one\n2\n3\n4\n5\n6\n7\n8\n9\nten\n";
+        assert_eq!(unified("old", "new", old, new),
+                   "--- old\n+++ new\n@@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n@@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+ten\n");
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::result;
+
+#[derive(Debug)]
+pub enum Error {
+    // A file could not be opened or read
+    Io(PathBuf, io::Error),
+    // A file was read but its contents did not have the expected shape
+    Decode(PathBuf, String),
+    // Asked to write config for a platform we don't know about
+    UnsupportedPlatform(String),
+    // Something we need is not there
+    MissingData(String),
+    // Config for a device that can't be written out safely
+    InvalidConfig(String, String),
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl Error {
+    // Distinct exit codes per failure class, borrowed from sysexits.h
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Io(_, _) => 74,
+            Error::Decode(_, _) => 65,
+            Error::UnsupportedPlatform(_) => 69,
+            Error::MissingData(_) => 66,
+            Error::InvalidConfig(_, _) => 65,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        match *self {
+            Error::Io(_, ref why) => why.kind() == io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref path, ref why) =>
+                write!(f, "{}: {}", path.display(), why),
+            Error::Decode(ref path, ref why) =>
+                write!(f, "could not decode {}: {}", path.display(), why),
+            Error::UnsupportedPlatform(ref name) =>
+                write!(f, "unsupported platform: {}", name),
+            Error::MissingData(ref what) =>
+                write!(f, "missing data: {}", what),
+            Error::InvalidConfig(ref device, ref why) =>
+                write!(f, "invalid config for {}: {}", device, why),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(_, _) => "I/O error",
+            Error::Decode(_, _) => "decode error",
+            Error::UnsupportedPlatform(_) => "unsupported platform",
+            Error::MissingData(_) => "missing data",
+            Error::InvalidConfig(_, _) => "invalid config",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Io(_, ref why) => Some(why),
+            _ => None,
+        }
+    }
+}
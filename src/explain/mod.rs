@@ -0,0 +1,137 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// A report of what glean made of every entry in /sys/class/net, so that
+// when a NIC doesn't get configured there's some way to find out why.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use ::config_drive::ConfigDrive;
+use ::live_networks::{LiveNetworks, Source};
+use ::sys::{Carrier, HardwareAddress, Reason, SysInterfaces};
+
+#[derive(Debug, Clone)]
+pub enum Status {
+    Accepted(Source),
+    Rejected(Reason),
+}
+
+#[derive(Debug, Clone)]
+pub struct InterfaceReport {
+    pub iface: String,
+    pub address: Option<HardwareAddress>,
+    pub status: Status,
+    // How an accepted interface got carrier, rejected ones say in their reason
+    pub carrier: Option<Carrier>,
+    // The networks it was configured from, or for a rejected NIC what
+    // config drive has for its MAC
+    pub network: Option<String>,
+    pub file: Option<PathBuf>,
+    pub file_exists: bool,
+    // Where the platform's existing config for it is, which needn't be file
+    pub configured_in: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub interfaces: Vec<InterfaceReport>,
+}
+
+impl Report {
+    pub fn new(sys: &SysInterfaces, config_drive: &Option<ConfigDrive>, live: &LiveNetworks) -> Report {
+        let network_for = |address: &Option<HardwareAddress>| {
+            match (config_drive, address) {
+                (&Some(ref drive), &Some(ref address)) =>
+                    drive.network.find_by_address(address).map(|device| device.origin()),
+                _ => None,
+            }
+        };
+
+        let mut interfaces = Vec::new();
+        for iface in sys.interfaces.iter() {
+            let file = live.interface_file(&iface.iface);
+            // The same device the plan reports, whichever source it came from
+            let source = match live.source(&iface.iface) {
+                Some(source) => source.clone(),
+                None => Source::Dhcp,
+            };
+            let network = match (&source, live.device(&iface.iface)) {
+                (&Source::Dhcp, _) | (_, None) => None,
+                (_, Some(device)) => Some(device.origin()),
+            };
+            interfaces.push(InterfaceReport {
+                iface: iface.iface.clone(),
+                network: network,
+                address: Some(iface.address.clone()),
+                status: Status::Accepted(source),
+                carrier: Some(iface.carrier.clone()),
+                file_exists: live.config_exists(&iface.iface),
+                configured_in: live.config_file(&iface.iface),
+                file: Some(file),
+            });
+        }
+        for rejected in sys.rejected.iter() {
+            interfaces.push(InterfaceReport {
+                iface: rejected.iface.clone(),
+                network: network_for(&rejected.address),
+                address: rejected.address.clone(),
+                status: Status::Rejected(rejected.reason.clone()),
+                carrier: None,
+                file: None,
+                file_exists: false,
+                configured_in: None,
+            });
+        }
+        interfaces.sort_by(|a, b| a.iface.cmp(&b.iface));
+        Report { interfaces: interfaces }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for iface in self.interfaces.iter() {
+            try!(writeln!(f, "{}", iface.iface));
+            match iface.address {
+                Some(ref address) => try!(writeln!(f, "    address: {}", address)),
+                None => try!(writeln!(f, "    address: unknown")),
+            }
+            match iface.status {
+                Status::Accepted(ref source) =>
+                    try!(writeln!(f, "    accepted: {}", source)),
+                Status::Rejected(ref reason) =>
+                    try!(writeln!(f, "    rejected: {}", reason)),
+            }
+            if let Some(ref carrier) = iface.carrier {
+                try!(writeln!(f, "    carrier: {}", carrier));
+            }
+            match (&iface.status, &iface.network) {
+                (&Status::Accepted(_), &Some(ref network)) => try!(writeln!(f, "    network: {}", network)),
+                (&Status::Accepted(_), &None) => try!(writeln!(f, "    network: none")),
+                (&Status::Rejected(_), &Some(ref network)) => try!(writeln!(f, "    config drive: {}", network)),
+                (&Status::Rejected(_), &None) => try!(writeln!(f, "    config drive: none")),
+            }
+            match (&iface.file, &iface.configured_in) {
+                (&Some(ref file), &Some(ref existing)) if iface.file_exists && existing != file =>
+                    try!(writeln!(f, "    file: {} (already configured in {}, not written)", file.display(), existing.display())),
+                (&Some(ref file), _) if iface.file_exists =>
+                    try!(writeln!(f, "    file: {} (already exists, not written)", file.display())),
+                (&Some(ref file), _) => try!(writeln!(f, "    file: {}", file.display())),
+                (&None, _) => {},
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Some things only want doing on the first boot of an instance: a user who
+// changes the hostname or prunes authorized_keys doesn't expect glean to put
+// them back on every reboot. Interface detection, on the other hand, has to
+// happen every boot since the hardware can change under an image. We tell
+// the two apart by keeping the uuid from meta_data.json of the last instance
+// we ran for in the state file, along with the tasks done for it, since
+// glean hostname and glean ssh-keys can be run on their own.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+use ::{Error, Result};
+use ::config_drive::meta_data::MetaData;
+use ::state::State;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    PerInstance,
+    PerBoot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Task {
+    Network,
+    Hostname,
+    SshKeys,
+}
+
+impl Task {
+    pub fn from_name(name: &str) -> Option<Task> {
+        match name {
+            "network" => Some(Task::Network),
+            "hostname" => Some(Task::Hostname),
+            "ssh-keys" => Some(Task::SshKeys),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Task::Network => "network",
+            Task::Hostname => "hostname",
+            Task::SshKeys => "ssh-keys",
+        }
+    }
+
+    pub fn frequency(&self) -> Frequency {
+        match *self {
+            Task::Network => Frequency::PerBoot,
+            Task::Hostname => Frequency::PerInstance,
+            Task::SshKeys => Frequency::PerInstance,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Instance {
+    root: PathBuf,
+    meta_data: Option<MetaData>,
+    state: Option<State>,
+}
+
+impl Instance {
+    pub fn new(root: &Option<String>, meta_data: Option<MetaData>) -> Result<Instance> {
+        let base_root_path = match root {
+            &Some(ref path) => PathBuf::from(path),
+            &None => PathBuf::from("/"),
+        };
+        Ok(Instance {
+            root: base_root_path,
+            meta_data: meta_data,
+            state: try!(State::load(root)),
+        })
+    }
+
+    pub fn uuid(&self) -> Option<String> {
+        return self.meta_data.as_ref().map(|meta_data| meta_data.uuid.clone());
+    }
+
+    // Without meta data there's no instance to speak of, and nothing for
+    // the per-instance tasks to work from either
+    pub fn should_run(&self, task: Task) -> bool {
+        match task.frequency() {
+            Frequency::PerBoot => true,
+            Frequency::PerInstance => match (self.uuid(), &self.state) {
+                (None, _) => false,
+                (uuid, &Some(ref state)) => !state.has_done(&uuid, task),
+                (_, &None) => true,
+            },
+        }
+    }
+
+    // Call once per-instance tasks are done, with the ones that were. Those
+    // done earlier for the same instance are kept.
+    pub fn record(&self, done: &[Task]) -> Result<()> {
+        let uuid = self.uuid();
+        if uuid.is_none() {
+            return Ok(());
+        }
+        let root = Some(self.root.to_string_lossy().into_owned());
+        // The state as it is now, not as it was when we started
+        let mut state = match try!(State::load(&root)) {
+            Some(state) => state,
+            None => State::empty(),
+        };
+        state.record(&uuid, done);
+        state.save(&root)
+    }
+
+    pub fn hostname(&self) -> Option<String> {
+        match self.meta_data {
+            Some(ref meta_data) => meta_data.hostname.clone().or(meta_data.name.clone()),
+            None => None,
+        }
+    }
+
+    pub fn set_hostname(&self, noop: bool) -> Result<()> {
+        let hostname = match self.hostname() {
+            Some(hostname) => hostname,
+            None => return Ok(()),
+        };
+        let path = self.root.join("etc/hostname");
+        info!("Setting hostname to {} in {}", hostname, path.display());
+        if noop {
+            return Ok(());
+        }
+        let mut file = try!(File::create(&path).map_err(|why| Error::Io(path.clone(), why)));
+        try!(write!(file, "{}\n", hostname).map_err(|why| Error::Io(path.clone(), why)));
+        // Only touch the running system if that's what we're configuring
+        if self.root == PathBuf::from("/") {
+            match Command::new("hostname").arg(&hostname).status() {
+                Err(why) => debug!("hostname error: {}", why),
+                _ => {},
+            };
+        }
+        Ok(())
+    }
+
+    // Adds any keys from meta_data.json that root's authorized_keys doesn't
+    // already have. Keys already there are left alone.
+    pub fn inject_ssh_keys(&self, noop: bool) -> Result<()> {
+        let keys = match self.meta_data {
+            Some(MetaData { public_keys: Some(ref keys), .. }) => keys,
+            _ => return Ok(()),
+        };
+        let ssh_dir = self.root.join("root/.ssh");
+        let path = ssh_dir.join("authorized_keys");
+        let mut current = String::new();
+        match File::open(&path).and_then(|mut f| f.read_to_string(&mut current)) {
+            Ok(_) => {},
+            Err(ref why) if why.kind() == ::std::io::ErrorKind::NotFound => {},
+            Err(why) => return Err(Error::Io(path, why)),
+        };
+        let mut names: Vec<&String> = keys.keys().collect();
+        names.sort();
+        let mut missing = Vec::new();
+        for name in names {
+            let key = keys[name].trim();
+            if !current.lines().any(|line| line.trim() == key) {
+                info!("Adding ssh key {} to {}", name, path.display());
+                missing.push(key);
+            }
+        }
+        if noop || missing.is_empty() {
+            return Ok(());
+        }
+
+        try!(fs::create_dir_all(&ssh_dir).map_err(|why| Error::Io(ssh_dir.clone(), why)));
+        try!(fs::set_permissions(&ssh_dir, fs::Permissions::from_mode(0o700)).map_err(|why| Error::Io(ssh_dir.clone(), why)));
+        let mut file = try!(OpenOptions::new().append(true).create(true).open(&path).map_err(|why| Error::Io(path.clone(), why)));
+        try!(fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).map_err(|why| Error::Io(path.clone(), why)));
+        if !current.is_empty() && !current.ends_with('\n') {
+            try!(write!(file, "\n").map_err(|why| Error::Io(path.clone(), why)));
+        }
+        for key in missing {
+            try!(write!(file, "{}\n", key).map_err(|why| Error::Io(path.clone(), why)));
+        }
+        Ok(())
+    }
+}
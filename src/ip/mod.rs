@@ -0,0 +1,168 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Addresses with a prefix length, for both the address an interface is given
+// and the destination of a route. network_info.json spells the prefix as a
+// netmask, dotted for IPv4 and as an address for IPv6, but a bare length is
+// accepted too since hand-written overrides often use one.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prefix {
+    address: IpAddr,
+    len: u8,
+}
+
+impl Prefix {
+    pub fn new(address: IpAddr, len: u8) -> Option<Prefix> {
+        if len > max_len(&address) {
+            return None;
+        }
+        Some(Prefix { address: address, len: len })
+    }
+
+    // The netmask has to be of the same family as the address and have its
+    // bits contiguous
+    pub fn from_netmask(address: IpAddr, netmask: &str) -> Option<Prefix> {
+        match netmask.trim_left_matches('/').parse::<u8>() {
+            Ok(len) => return Prefix::new(address, len),
+            Err(_) => {},
+        }
+        let len = match (address, netmask.parse::<IpAddr>()) {
+            (IpAddr::V4(_), Ok(IpAddr::V4(mask))) => mask_len(&mask.octets()),
+            (IpAddr::V6(_), Ok(IpAddr::V6(mask))) => mask_len(&mask.octets()),
+            _ => None,
+        };
+        match len {
+            Some(len) => Prefix::new(address, len),
+            None => None,
+        }
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.len
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        match self.address {
+            IpAddr::V4(_) => false,
+            IpAddr::V6(_) => true,
+        }
+    }
+
+    // 0.0.0.0/0 or ::/0
+    pub fn is_default(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn netmask(&self) -> IpAddr {
+        match self.address {
+            IpAddr::V4(_) => {
+                let mut octets = [0; 4];
+                fill_mask(&mut octets, self.len);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            },
+            IpAddr::V6(_) => {
+                let mut octets = [0; 16];
+                fill_mask(&mut octets, self.len);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            },
+        }
+    }
+
+    // The address with the host bits cleared
+    pub fn network(&self) -> IpAddr {
+        match (self.address, self.netmask()) {
+            (IpAddr::V4(address), IpAddr::V4(mask)) => {
+                let mut octets = address.octets();
+                for (octet, mask) in octets.iter_mut().zip(mask.octets().iter()) {
+                    *octet &= *mask;
+                }
+                IpAddr::V4(Ipv4Addr::from(octets))
+            },
+            (IpAddr::V6(address), IpAddr::V6(mask)) => {
+                let mut octets = address.octets();
+                for (octet, mask) in octets.iter_mut().zip(mask.octets().iter()) {
+                    *octet &= *mask;
+                }
+                IpAddr::V6(Ipv6Addr::from(octets))
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    // IPv6 has no broadcast address
+    pub fn broadcast(&self) -> Option<Ipv4Addr> {
+        match (self.address, self.netmask()) {
+            (IpAddr::V4(address), IpAddr::V4(mask)) => {
+                let mut octets = address.octets();
+                for (octet, mask) in octets.iter_mut().zip(mask.octets().iter()) {
+                    *octet |= !*mask;
+                }
+                Some(Ipv4Addr::from(octets))
+            },
+            _ => None,
+        }
+    }
+}
+
+// address/len
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.len)
+    }
+}
+
+fn max_len(address: &IpAddr) -> u8 {
+    match *address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+fn mask_len(octets: &[u8]) -> Option<u8> {
+    let mut len = 0;
+    let mut ended = false;
+    for octet in octets.iter() {
+        let ones = (!octet).leading_zeros() as u8;
+        if ended && *octet != 0 {
+            return None;
+        }
+        if ones < 8 {
+            // Anything set after the leading ones is a hole in the mask
+            if octet.wrapping_shl(ones as u32) != 0 {
+                return None;
+            }
+            ended = true;
+        }
+        len += ones;
+    }
+    Some(len)
+}
+
+fn fill_mask(octets: &mut [u8], len: u8) {
+    let mut remaining = len as usize;
+    for octet in octets.iter_mut() {
+        let bits = if remaining >= 8 { 8 } else { remaining };
+        *octet = if bits == 0 { 0 } else { !0u8 << (8 - bits) };
+        remaining -= bits;
+    }
+}
@@ -16,8 +16,29 @@
 #[macro_use]
 extern crate log;
 extern crate glob;
+extern crate libc;
 extern crate rustc_serialize;
 
+pub use error::{Error, Result};
+pub use pipeline::{run, GleanConfig, GleanConfigBuilder, NetworkReport, Report};
+
+mod checksum;
+mod diff;
+mod error;
+pub mod ip;
+pub mod model;
 pub mod sys;
 pub mod config_drive;
+pub mod overrides;
 pub mod live_networks;
+pub mod template;
+pub mod writers;
+pub mod explain;
+pub mod plan;
+pub mod state;
+pub mod instance;
+pub mod pipeline;
+pub mod settings;
+pub mod cmdline;
+#[cfg(feature = "testing")]
+pub mod testing;
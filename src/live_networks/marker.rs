@@ -0,0 +1,66 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Every file glean writes starts with a marker line carrying a checksum of
+// the rest of the file. That lets us tell our own untouched files, which we
+// can safely regenerate, apart from files a human wrote or edited, which we
+// must leave alone.
+
+use ::checksum::sha256;
+
+static BULLET: &'static str = "Generated by glean";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ownership {
+    // Ours and unmodified since we wrote it
+    Glean,
+    // Has our marker but the content no longer matches its checksum
+    Edited,
+    // Has our marker from before we recorded checksums
+    Legacy,
+    // Not written by glean
+    Other,
+}
+
+fn split_marker(content: &str) -> (&str, &str) {
+    match content.find('\n') {
+        Some(index) => (&content[..index], &content[index + 1..]),
+        None => (content, ""),
+    }
+}
+
+pub fn mark(body: &str) -> String {
+    let mut body = String::from(body);
+    if !body.ends_with('\n') {
+        body.push('\n');
+    }
+    return format!("# {} sha256:{}\n{}", BULLET, sha256(body.as_bytes()), body);
+}
+
+pub fn ownership(content: &str) -> Ownership {
+    let (first, body) = split_marker(content);
+    let prefix = format!("# {}", BULLET);
+    if !first.starts_with(&prefix) {
+        return Ownership::Other;
+    }
+    let checksum = first[prefix.len()..].trim();
+    if checksum.is_empty() {
+        return Ownership::Legacy;
+    }
+    if checksum == format!("sha256:{}", sha256(body.as_bytes())) {
+        return Ownership::Glean;
+    }
+    return Ownership::Edited;
+}
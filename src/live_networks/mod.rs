@@ -13,237 +13,417 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::path::PathBuf;
-use std::fs;
-use std::process::Command;
+use std::fs::{self, File};
+use std::io::prelude::*;
 
+use ::{Error, Result};
+use ::diff;
 use ::config_drive::ConfigDrive;
+use ::model::{Config, Device, Kind};
+use ::overrides::Overrides;
 use ::sys::SysInterfaces;
-use self::interface_type::InterfaceType;
-use self::interface_type::InterfaceType::{Static, Dhcp};
-use self::Platform::{RedHat, Debian};
+use ::writers::{Registry, Writer};
+use self::marker::Ownership;
 
-mod interface_type;
+mod marker;
 
-#[derive(Debug)]
 pub struct LiveNetworks {
-    interfaces: Vec<InterfaceType>,
-    platform: Platform,
-    root: PathBuf,
+    devices: Vec<Device>,
+    sources: BTreeMap<String, Source>,
+    // Interfaces we have config for but that are gone from this host
+    stale: Vec<String>,
+    platform: String,
+    writer: Box<Writer>,
+}
+
+impl fmt::Debug for LiveNetworks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LiveNetworks")
+            .field("devices", &self.devices)
+            .field("sources", &self.sources)
+            .field("stale", &self.stale)
+            .field("platform", &self.platform)
+            .finish()
+    }
+}
+
+// Where an interface's config came from, with the ids of the networks that
+// supplied its addresses, or of the link for one with no addresses of its own
+#[derive(Debug, Clone)]
+pub enum Source {
+    KernelCmdline(String),
+    Overrides(String),
+    ConfigDrive(String),
+    Dhcp,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Source::KernelCmdline(ref id) => write!(f, "from kernel command line {}", id),
+            Source::Overrides(ref id) => write!(f, "from overrides {}", id),
+            Source::ConfigDrive(ref id) => write!(f, "from config drive {}", id),
+            Source::Dhcp => write!(f, "dhcp"),
+        }
+    }
 }
 
 impl LiveNetworks {
 
-    pub fn new(interfaces: &SysInterfaces, config_drive: Option<ConfigDrive>, root: &Option<String>) -> LiveNetworks {
+    pub fn new(interfaces: &SysInterfaces, config_drive: Option<ConfigDrive>, overrides: Option<Overrides>, root: &Option<String>, distro: &Option<String>) -> Result<LiveNetworks> {
+        LiveNetworks::with_registry(interfaces, config_drive, overrides, root, distro, &Registry::builtin())
+    }
 
-      let mut live_ifaces = Vec::new();
+    // Like new, but with writers for more platforms than glean's own
+    pub fn with_registry(interfaces: &SysInterfaces, config_drive: Option<ConfigDrive>, overrides: Option<Overrides>, root: &Option<String>, distro: &Option<String>, registry: &Registry) -> Result<LiveNetworks> {
+
+      // Overrides first, so that they win, and ip= ahead of overrides.json
+      let mut configs: Vec<(Config, fn(String) -> Source)> = Vec::new();
+      if let Some(overrides) = overrides {
+          configs.push((overrides.kernel_cmdline, Source::KernelCmdline));
+          configs.push((overrides.network, Source::Overrides));
+      }
+      if let Some(drive) = config_drive {
+          configs.push((drive.network, Source::ConfigDrive));
+      }
+
+      let mut devices = Vec::new();
+      let mut sources = BTreeMap::new();
+      // For each config, the kernel names its physical devices turned out to have
+      let mut kernel_names: Vec<HashMap<String, String>> = configs.iter().map(|_| HashMap::new()).collect();
       for iface in interfaces.interfaces.iter() {
-          match config_drive {
-              Some(ref drive) =>
-                  match drive.interface_map.get(&iface.address) {
-                      Some(network) => {
-                          let mut new_net = network.clone();
-                          new_net.id = iface.iface.clone();
-                          new_net.link = iface.address.clone();
-                          live_ifaces.push(Static(new_net));
-                      },
-                      None => live_ifaces.push(Dhcp(iface.iface.clone())),
+          let found = configs.iter().enumerate()
+              .filter_map(|(index, &(ref config, source))| config.find(iface).map(|device| (index, device, source)))
+              .next();
+          let source = match found {
+              Some((index, device, source)) => {
+                  let mut device = device.clone();
+                  kernel_names[index].insert(device.id.clone(), iface.iface.clone());
+                  device.name = iface.iface.clone();
+                  device.hardware_address = Some(iface.address.clone());
+                  let source = source(device.origin());
+                  devices.push(device);
+                  source
+              },
+              None => {
+                  devices.push(Device::dhcp(iface));
+                  Source::Dhcp
+              },
+          };
+          info!("{}: {}", iface.iface, source);
+          sources.insert(iface.iface.clone(), source);
+      }
+      // Bonds and vlans don't exist until they are configured, so they come
+      // from the sources alone, built on top of the interfaces found above
+      for (index, &(ref config, source)) in configs.iter().enumerate() {
+          for device in config.virtual_devices() {
+              let device = match resolve_members(device, &kernel_names[index], config) {
+                  Some(device) => device,
+                  None => {
+                      debug!("Skipping {}, none of its members are here", device.id);
+                      continue;
                   },
-              None => live_ifaces.push(Dhcp(iface.iface.clone())),
+              };
+              if sources.contains_key(&device.name) {
+                  continue;
+              }
+              let source = source(device.origin());
+              info!("{}: {}", device.name, source);
+              sources.insert(device.name.clone(), source);
+              devices.push(device);
           }
       }
-      live_ifaces.sort();
+      devices.sort_by(|a, b| a.name.cmp(&b.name));
 
       let base_root_path = match root {
           &Some(ref path) => PathBuf::from(path),
           &None => PathBuf::from("/"),
       };
 
-      let platform;
-      match fs::metadata(base_root_path.clone().join("etc/network")) {
-          Ok(metadata) => {
-              if metadata.is_dir() {
-                  platform = Debian;
-              } else {
-                  platform = RedHat;
-              }
-          },
-          Err(_) => {
-              platform = RedHat;
-          },
+      let platform = match distro {
+          &Some(ref name) => try!(registry.resolve(name)),
+          &None => try!(registry.detect(&base_root_path)),
       };
-      LiveNetworks {
-          interfaces: live_ifaces,
+      let mut live_networks = LiveNetworks {
+          devices: devices,
+          sources: sources,
+          stale: Vec::new(),
+          writer: try!(registry.writer(&platform, &base_root_path)),
           platform: platform,
-          root: base_root_path,
+      };
+      if interfaces.is_available() {
+          live_networks.stale = live_networks.writer.configured_interfaces().into_iter()
+              .filter(|iface| !interfaces.exists(iface) && !live_networks.devices.iter().any(|device| device.name == *iface))
+              .collect();
+      } else {
+          warn!("No interfaces in sysfs, leaving config for missing ones alone");
       }
+      Ok(live_networks)
+    }
+
+    pub fn get_output(&self) -> Result<FileList> {
+        info!("Platform {}", self.platform);
+        let mut file_list = try!(get_output_files(&*self.writer, &self.devices));
+        file_list.files.extend(get_stale_files(&*self.writer, &self.stale));
+        return Ok(file_list);
     }
 
-    pub fn get_output(&self) -> FileList {
-        println!("{:?}", self.platform);
-        match self.platform {
-            RedHat => {
-                let w = RedHatWriter{ root: self.root.clone() };
-                return get_output_files(&w as &Writer, &self.interfaces);
+    pub fn platform_name(&self) -> String {
+        return self.platform.clone();
+    }
+
+    pub fn source(&self, iface: &String) -> Option<&Source> {
+        return self.sources.get(iface);
+    }
+
+    // The device an interface will be configured as
+    pub fn device(&self, iface: &String) -> Option<&Device> {
+        return self.devices.iter().find(|device| device.name == *iface);
+    }
+
+    pub fn interface_file(&self, iface: &String) -> PathBuf {
+        return self.writer.interface_file(iface);
+    }
+
+    pub fn config_exists(&self, iface: &String) -> bool {
+        return self.writer.config_exists(iface);
+    }
+
+    pub fn config_file(&self, iface: &String) -> Option<PathBuf> {
+        return self.writer.config_file(iface);
+    }
+}
+
+// A bond or vlan with its members given kernel names, or None if none of
+// them are on this host
+fn resolve_members(device: &Device, kernel_names: &HashMap<String, String>, config: &Config) -> Option<Device> {
+    let mut device = device.clone();
+    device.kind = match device.kind.clone() {
+        Kind::Bond { slaves, mode } => {
+            let slaves: Vec<String> = slaves.iter().filter_map(|id| kernel_names.get(id).cloned()).collect();
+            if slaves.is_empty() {
+                return None;
             }
-            Debian => {
-                let w = DebianWriter{ root: self.root.clone() };
-                return get_output_files(&w as &Writer, &self.interfaces);
+            Kind::Bond { slaves: slaves, mode: mode }
+        },
+        Kind::Vlan { parent, vlan_id } => {
+            // A vlan can sit on a bond as well as on an interface
+            let parent = match kernel_names.get(&parent) {
+                Some(name) => name.clone(),
+                None => match config.virtual_devices().iter().find(|other| other.id == parent) {
+                    Some(bond) => bond.name.clone(),
+                    None => return None,
+                },
+            };
+            if device.name.is_empty() {
+                device.name = format!("{}.{}", parent, vlan_id);
             }
-        };
-    }
+            Kind::Vlan { parent: parent, vlan_id: vlan_id }
+        },
+        Kind::Ethernet => return None,
+    };
+    Some(device)
 }
 
-fn get_output_files(writer: &Writer, interfaces: &Vec<InterfaceType>) -> FileList {
+fn get_output_files(writer: &Writer, devices: &Vec<Device>) -> Result<FileList> {
     let mut file_list = Vec::<FileToWrite>::new();
-    for interface in interfaces.iter() {
-        let iface = match interface {
-            &Static(ref network) => network.id.clone(),
-            &Dhcp(ref iface) => iface.clone(),
+    // Which device each file is for, since names that differ can still end
+    // up in the same file
+    let mut owners: HashMap<PathBuf, String> = HashMap::new();
+    for device in devices.iter() {
+        let iface = device.name.clone();
+        let mut file = try!(writer.generate_config(device));
+        let extra = try!(writer.generate_extra_config(device));
+        for path in Some(&file.path).into_iter().chain(extra.iter().map(|file| &file.path)) {
+            if let Some(other) = owners.insert(path.clone(), iface.clone()) {
+                return Err(Error::InvalidConfig(iface, format!("its config would overwrite {}'s in {}", other, path.display())));
+            }
+        }
+        let removed: Vec<FileToWrite> = writer.extra_files(&iface).into_iter()
+            .filter(|path| !extra.iter().any(|file| file.path == *path))
+            .filter_map(remove_if_ours)
+            .collect();
+        // Config in a file of ours that is going away doesn't count
+        let configured_elsewhere = match writer.config_file(&iface) {
+            Some(ref path) if removed.iter().any(|file| file.path == *path) => false,
+            _ => writer.config_exists(&iface),
         };
-        if ! writer.config_exists(&iface) {
-            println!("Don't have: {}", iface);
-            file_list.push(writer.generate_config(&iface, &interface));
-        } else {
-            println!("Already have: {}", iface);
+        file.action = choose_action(&file, configured_elsewhere);
+        file_list.push(file);
+        file_list.extend(removed);
+        for mut file in extra.into_iter() {
+            file.action = choose_action(&file, false);
+            file_list.push(file);
         }
     }
-    FileList { files: file_list }
-}
-
-#[derive(Debug)]
-pub struct FileList {
-   files: Vec<FileToWrite>,
+    Ok(FileList { files: file_list })
 }
 
-impl FileList {
-   pub fn write(&self) {
-      println!("{:?}", self.files);
-   }
+// Only files we wrote and nobody has touched since are regenerated. Nothing
+// is written where the platform has config for the interface elsewhere.
+fn choose_action(file: &FileToWrite, configured_elsewhere: bool) -> Action {
+    match read_file(&file.path) {
+        Some(ref current) => match marker::ownership(current) {
+            Ownership::Glean if *current == file.content => {
+                info!("Already have: {}", file.path.display());
+                Action::Skip
+            },
+            Ownership::Glean => {
+                info!("Regenerating: {}", file.path.display());
+                Action::Replace
+            },
+            // There's no telling whether these were edited, but every glean
+            // before checksums wrote them, so they are taken as ours once
+            // and get a checksum from now on
+            Ownership::Legacy => {
+                warn!("Regenerating {}, written before glean recorded checksums", file.path.display());
+                Action::Replace
+            },
+            Ownership::Edited | Ownership::Other => {
+                info!("Already have, not generated by glean: {}", file.path.display());
+                Action::Skip
+            },
+        },
+        // Configured somewhere else, or there but unreadable
+        None if configured_elsewhere || file.path.exists() => {
+            info!("Already have config for: {}", file.path.display());
+            Action::Skip
+        },
+        None => {
+            info!("Don't have: {}", file.path.display());
+            Action::Create
+        },
+    }
 }
 
-
-#[derive(Debug)]
-struct FileToWrite {
-    path: PathBuf,
-    content: String,
+// Config we wrote for interfaces that no longer exist only makes ifup wait
+// on devices that will never appear. Anything else is left alone.
+fn get_stale_files(writer: &Writer, stale: &Vec<String>) -> Vec<FileToWrite> {
+    let mut files = Vec::new();
+    for iface in stale.iter() {
+        info!("No longer have interface: {}", iface);
+        files.extend(remove_if_ours(writer.interface_file(iface)));
+        for path in writer.extra_files(iface).into_iter() {
+            files.extend(remove_if_ours(path));
+        }
+    }
+    return files;
 }
 
-#[derive(Debug)]
-enum Platform {
-  RedHat,
-  Debian,
+fn remove_if_ours(path: PathBuf) -> Option<FileToWrite> {
+    let ownership = read_file(&path).map(|current| marker::ownership(&current));
+    match ownership {
+        Some(Ownership::Glean) | Some(Ownership::Legacy) => {
+            info!("Removing: {}", path.display());
+            Some(FileToWrite {
+                path: path,
+                content: String::new(),
+                action: Action::Remove,
+            })
+        },
+        Some(_) => {
+            debug!("Leaving: {}", path.display());
+            None
+        },
+        None => None,
+    }
 }
 
-struct RedHatWriter {
-    root: PathBuf,
+fn read_file(path: &PathBuf) -> Option<String> {
+    let mut content = String::new();
+    match File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+        Ok(_) => Some(content),
+        Err(why) => {
+            debug!("couldn't read {}: {}", path.display(), why);
+            None
+        },
+    }
 }
 
-struct DebianWriter {
-    root: PathBuf,
+#[derive(Debug)]
+pub struct FileList {
+   files: Vec<FileToWrite>,
 }
 
-static BULLET: &'static str = "Generated by glean";
-
-trait Writer {
-    fn interface_file(&self, iface: &String) -> PathBuf;
-    fn config_exists(&self, iface: &String) -> bool;
-    fn generate_config(&self, iface: &String, interface: &InterfaceType) -> FileToWrite;
-}
+impl FileList {
+   pub fn write(&self) -> Result<()> {
+      for file in self.files.iter() {
+          if file.action == Action::Skip {
+              continue;
+          }
+          if file.action == Action::Remove {
+              try!(fs::remove_file(&file.path).map_err(|why| Error::Io(file.path.clone(), why)));
+              info!("Removed {}", file.path.display());
+              continue;
+          }
+          if let Some(parent) = file.path.parent() {
+              try!(fs::create_dir_all(parent).map_err(|why| Error::Io(parent.to_path_buf(), why)));
+          }
+          let mut f = try!(File::create(&file.path).map_err(|why| Error::Io(file.path.clone(), why)));
+          try!(f.write_all(file.content.as_bytes()).map_err(|why| Error::Io(file.path.clone(), why)));
+          info!("Wrote {}", file.path.display());
+      }
+      Ok(())
+   }
 
-impl Writer for RedHatWriter {
-    fn interface_file(&self, iface: &String) -> PathBuf {
-      let mut path_str = String::from("etc/sysconfig/network-scripts/ifcfg-");
-      path_str.push_str(iface);
-      return self.root.clone().join(path_str);
-    }
+   pub fn files(&self) -> &Vec<FileToWrite> {
+      return &self.files;
+   }
 
-    fn config_exists(&self, iface: &String) -> bool {
-      match fs::metadata(self.interface_file(&iface)) {
-          Ok(metadata) => return metadata.is_file(),
-          Err(_) => return false,
+   // Unified diff of each file on disk against what we'd put there. Files
+   // we'd skip are included, marked as such, so it's clear what glean
+   // would have wanted even where it leaves things alone.
+   pub fn diff(&self) -> String {
+      let mut out = String::new();
+      for file in self.files.iter() {
+          let path = file.path.to_string_lossy().into_owned();
+          let (old_label, current) = match read_file(&file.path) {
+              Some(current) => (path.clone(), current),
+              None => (String::from("/dev/null"), String::new()),
+          };
+          let new_label = format!("{}\t({})", path, file.action);
+          out.push_str(&diff::unified(&old_label, &new_label, &current, &file.content));
       }
-    }
-
-    fn generate_config(&self, iface: &String, interface: &InterfaceType) -> FileToWrite {
-        let filename = self.interface_file(&iface);
-        let content;
-        match interface {
-            &Dhcp(_) => { content = format!("# {bullet}
-DEVICE={iface}
-BOOTPROTO=dhcp
-ONBOOT=yes
-NM_CONTROLLED=no
-TYPE=Ethernet", bullet=BULLET, iface=iface) },
-            &Static(ref network) => {
-                content = format!("# {bullet}
-DEVICE={iface}
-BOOTPROTO=static
-HWADDR={hwaddr}
-IPADDR={ip_address}
-NETMASK={netmask}
-ONBOOT=yes
-NM_CONTROLLED=no", bullet=BULLET, iface=iface, ip_address=network.ip_address, netmask=network.netmask, hwaddr=network.link);
-            }
-        };
-        return FileToWrite {
-            path: filename,
-            content: content,
-        };
-    }
+      return out;
+   }
 }
 
-impl Writer for DebianWriter {
-    fn interface_file(&self, iface: &String) -> PathBuf {
-        return self.root.clone().join("etc/network/interfaces.d").join(iface);
-    }
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Create,
+    Skip,
+    Replace,
+    Remove,
+}
 
-    fn config_exists(&self, iface: &String) -> bool {
-      match Command::new("ifquery").arg(iface).status() {
-        Err(why) => { debug!("ifquery error: {}", why); return false ; }
-        Ok(status) => return status.success(),
-      }
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Action::Create => write!(f, "create"),
+            Action::Skip => write!(f, "skip"),
+            Action::Replace => write!(f, "replace"),
+            Action::Remove => write!(f, "remove"),
+        }
     }
-    fn generate_config(&self, iface: &String, interface: &InterfaceType) -> FileToWrite {
-        let mut content;
-        match interface {
-            &Dhcp(_) => { content = format!("# {bullet}
-aut0 {iface}
-iface {iface} inet dhcp", bullet=BULLET, iface=iface) },
-            &Static(ref network) => {
-                let link_type;
-                if network.network_type == "ipv6" {
-                    link_type = "inet6";
-                } else {
-                    link_type = "inet";
-                }
-                content = String::from(format!("# {bullet}
-aut0 {iface}
-iface {iface} {link_type} static
-    address {ip_address}
-    netmask {netmask}", bullet=BULLET, iface=iface, link_type=link_type, ip_address=network.ip_address, netmask=network.netmask));
-                for ref route in network.routes.iter() {
-                    if route.network == "0.0.0.0" && route.netmask == "0.0.0.0" {
-                        content.push_str(&format!("
-    gateway {gateway}", gateway=route.gateway));
-                    } else {
-                        content.push_str(&format!("
-    post-up route add -net {net} netmask {mask} gw {gw} || true\n",
-                            net=route.network, mask=route.netmask,
-                            gw=route.gateway));
-                        content.push_str(&format!("
-    pre-down route add -net {net} netmask {mask} gw {gw} || true\n",
-                            net=route.network, mask=route.netmask,
-                            gw=route.gateway));
-                    }
-                }
+}
 
-            }
-        };
+#[derive(Debug)]
+pub struct FileToWrite {
+    pub path: PathBuf,
+    pub content: String,
+    pub action: Action,
+}
 
-        return FileToWrite {
-            path: self.interface_file(&iface),
-            content: content,
-        };
+impl FileToWrite {
+    // Writers hand over the body, the ownership marker is added here
+    pub fn new(path: PathBuf, body: String) -> FileToWrite {
+        FileToWrite {
+            path: path,
+            content: marker::mark(&body),
+            action: Action::Create,
+        }
     }
 }
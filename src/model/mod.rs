@@ -0,0 +1,146 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// What the network should look like, independent of where that was learned
+// and of how a distro wants it written down. Data sources turn what they know
+// into a Config, LiveNetworks matches its devices up with the interfaces in
+// sysfs, and writers render the result.
+
+use std::net::IpAddr;
+
+use ::ip::Prefix;
+use ::sys::{HardwareAddress, Interface};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    Ethernet,
+    Bond { slaves: Vec<String>, mode: Option<String> },
+    Vlan { parent: String, vlan_id: u16 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Method {
+    Static,
+    Dhcp,
+    // Up, but with no addresses of its own, like a bond slave
+    Manual,
+}
+
+impl Method {
+    // As templates and the plan name it
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Method::Static => "static",
+            Method::Dhcp => "dhcp",
+            Method::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub destination: Prefix,
+    pub gateway: IpAddr,
+}
+
+#[derive(Debug, Clone)]
+pub struct Device {
+    // What the source calls this device. Bonds and vlans refer to their
+    // members by it, which LiveNetworks turns into kernel names.
+    pub id: String,
+    // The kernel name. Sources that don't know it use the id, or leave it
+    // empty for a vlan that should be named after its parent.
+    pub name: String,
+    // Ids of the source's networks the addresses came from, for reporting
+    pub networks: Vec<String>,
+    pub hardware_address: Option<HardwareAddress>,
+    pub kind: Kind,
+    pub mtu: Option<u16>,
+    pub method: Method,
+    pub addresses: Vec<Prefix>,
+    pub routes: Vec<Route>,
+    pub dns: Vec<IpAddr>,
+    // Name of the bond this device is enslaved to
+    pub master: Option<String>,
+}
+
+impl Device {
+    // A plain interface we know nothing about
+    pub fn dhcp(iface: &Interface) -> Device {
+        Device {
+            id: iface.iface.clone(),
+            name: iface.iface.clone(),
+            networks: Vec::new(),
+            hardware_address: Some(iface.address.clone()),
+            kind: Kind::Ethernet,
+            mtu: None,
+            method: Method::Dhcp,
+            addresses: Vec::new(),
+            routes: Vec::new(),
+            dns: Vec::new(),
+            master: None,
+        }
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        self.kind != Kind::Ethernet
+    }
+
+    // What to report this device's config as coming from
+    pub fn origin(&self) -> String {
+        match self.networks.is_empty() {
+            true => self.id.clone(),
+            false => self.networks.join(","),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub devices: Vec<Device>,
+    pub dns: Vec<IpAddr>,
+}
+
+impl Config {
+    // The physical device in this config for an interface in sysfs. A name
+    // given by the source wins over the hardware address.
+    pub fn find(&self, iface: &Interface) -> Option<&Device> {
+        self.devices.iter()
+            .find(|device| !device.is_virtual() && device.name == iface.iface)
+            .or_else(|| self.find_by_address(&iface.address))
+    }
+
+    // An exact match wins over one that only matches(), e.g. by InfiniBand GUID
+    pub fn find_by_address(&self, address: &HardwareAddress) -> Option<&Device> {
+        let physical: Vec<&Device> = self.devices.iter().filter(|device| !device.is_virtual()).collect();
+        physical.iter().find(|device| device.hardware_address.as_ref() == Some(address))
+            .or_else(|| physical.iter().find(|device| match device.hardware_address {
+                Some(ref other) => other.matches(address),
+                None => false,
+            }))
+            .map(|device| *device)
+    }
+
+    pub fn virtual_devices(&self) -> Vec<&Device> {
+        self.devices.iter().filter(|device| device.is_virtual()).collect()
+    }
+
+    pub fn clear_dns(&mut self) {
+        self.dns.clear();
+        for device in self.devices.iter_mut() {
+            device.dns.clear();
+        }
+    }
+}
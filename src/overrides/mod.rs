@@ -0,0 +1,53 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Local overrides for static network config, read from
+// /etc/glean/overrides.json under the root. The file has the same shape as
+// network_info.json. A link is matched to an interface by its
+// ethernet_mac_address or, if it has one, by its name, and the networks
+// attached to it win over whatever config drive says about that interface.
+// Devices from ip= on the kernel command line ride along with them, and win
+// over the file in turn.
+
+use std::path::PathBuf;
+
+use ::Result;
+use ::config_drive::network_info::NetworkInfo;
+use ::model::Config;
+
+#[derive(Debug, Clone)]
+pub struct Overrides {
+    pub network: Config,
+    pub kernel_cmdline: Config,
+}
+
+impl Overrides {
+    pub fn new(root: &Option<String>) -> Result<Option<Overrides>> {
+        let base_root_path = match root {
+            &Some(ref path) => PathBuf::from(path),
+            &None => PathBuf::from("/"),
+        };
+        let path = base_root_path.join("etc/glean/overrides.json");
+        debug!("Trying Path: {}", path.display());
+        match NetworkInfo::from_path(&path) {
+            Ok(info) => Ok(Some(Overrides {
+                network: info.to_config(),
+                kernel_cmdline: Config { devices: Vec::new(), dns: Vec::new() },
+            })),
+            Err(ref why) if why.is_not_found() => Ok(None),
+            Err(why) => Err(why),
+        }
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The whole of a glean run, for the glean binary and for anything that
+// would rather embed glean than exec it: find the interfaces, work out their
+// config from config drive and overrides, write it, and do the per-instance
+// tasks.
+
+use std::rc::Rc;
+use std::time::Duration;
+
+use ::Result;
+use ::config_drive::ConfigDrive;
+use ::config_drive::meta_data::MetaData;
+use ::explain;
+use ::instance::{Instance, Task};
+use ::live_networks::{FileList, LiveNetworks};
+use ::model::{Config, Device};
+use ::overrides::Overrides;
+use ::plan::Plan;
+use ::state::State;
+use ::sys::{CarrierProbe, Filter, Rule, Strategy, SysInterfaces};
+use ::writers::Registry;
+
+#[derive(Debug, Clone)]
+pub struct GleanConfig {
+    // Where the system being configured is mounted, / if None
+    pub root: Option<String>,
+    // Platform name or alias, detected from the root if None
+    pub distro: Option<String>,
+    // The platforms distro is looked up in, shared since it can't be cloned
+    pub registry: Rc<Registry>,
+    // Only this interface, rather than everything in /sys/class/net
+    pub interface: Option<String>,
+    pub filter: Filter,
+    pub probe: CarrierProbe,
+    // Work everything out but change nothing
+    pub noop: bool,
+    // Which of network config, hostname and ssh keys to do
+    pub tasks: Vec<Task>,
+    // Whether to use the nameservers config drive hands out
+    pub dns: bool,
+    // Leave config drive and overrides.json out of it
+    pub dhcp: bool,
+    // Config that wins over overrides.json, e.g. from ip= on the kernel
+    // command line
+    pub devices: Vec<Device>,
+}
+
+impl GleanConfig {
+    pub fn builder() -> GleanConfigBuilder {
+        GleanConfigBuilder { config: GleanConfig::default() }
+    }
+
+    pub fn runs(&self, task: Task) -> bool {
+        self.tasks.contains(&task)
+    }
+}
+
+impl Default for GleanConfig {
+    // What the glean binary does with no options
+    fn default() -> GleanConfig {
+        GleanConfig {
+            root: None,
+            distro: None,
+            registry: Rc::new(Registry::builtin()),
+            interface: None,
+            filter: Filter::new(),
+            probe: CarrierProbe::new(Duration::from_secs(10), Strategy::Events),
+            noop: false,
+            tasks: vec![Task::Network, Task::Hostname, Task::SshKeys],
+            dns: true,
+            dhcp: false,
+            devices: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GleanConfigBuilder {
+    config: GleanConfig,
+}
+
+impl GleanConfigBuilder {
+    pub fn root(mut self, root: &str) -> GleanConfigBuilder {
+        self.config.root = Some(String::from(root));
+        self
+    }
+
+    pub fn distro(mut self, distro: &str) -> GleanConfigBuilder {
+        self.config.distro = Some(String::from(distro));
+        self
+    }
+
+    // Platforms to write config for besides glean's own
+    pub fn registry(mut self, registry: Registry) -> GleanConfigBuilder {
+        self.config.registry = Rc::new(registry);
+        self
+    }
+
+    pub fn interface(mut self, interface: &str) -> GleanConfigBuilder {
+        self.config.interface = Some(String::from(interface));
+        self
+    }
+
+    pub fn include(mut self, rule: Rule) -> GleanConfigBuilder {
+        self.config.filter.include.push(rule);
+        self
+    }
+
+    pub fn exclude(mut self, rule: Rule) -> GleanConfigBuilder {
+        self.config.filter.exclude.push(rule);
+        self
+    }
+
+    // Replaces the include rules given so far, leaving the exclude rules
+    pub fn include_only(mut self, rules: Vec<Rule>) -> GleanConfigBuilder {
+        self.config.filter.include = rules;
+        self
+    }
+
+    // Replaces the include and exclude rules given so far
+    pub fn filter(mut self, filter: Filter) -> GleanConfigBuilder {
+        self.config.filter = filter;
+        self
+    }
+
+    pub fn carrier(mut self, timeout: Duration, strategy: Strategy) -> GleanConfigBuilder {
+        self.config.probe = CarrierProbe::new(timeout, strategy);
+        self
+    }
+
+    pub fn carrier_timeout(mut self, timeout: Duration) -> GleanConfigBuilder {
+        self.config.probe.timeout = timeout;
+        self
+    }
+
+    pub fn carrier_strategy(mut self, strategy: Strategy) -> GleanConfigBuilder {
+        self.config.probe.strategy = strategy;
+        self
+    }
+
+    pub fn dns(mut self, dns: bool) -> GleanConfigBuilder {
+        self.config.dns = dns;
+        self
+    }
+
+    pub fn noop(mut self, noop: bool) -> GleanConfigBuilder {
+        self.config.noop = noop;
+        self
+    }
+
+    pub fn tasks(mut self, tasks: &[Task]) -> GleanConfigBuilder {
+        self.config.tasks = tasks.to_vec();
+        self
+    }
+
+    pub fn dhcp(mut self, dhcp: bool) -> GleanConfigBuilder {
+        self.config.dhcp = dhcp;
+        self
+    }
+
+    pub fn device(mut self, device: Device) -> GleanConfigBuilder {
+        self.config.devices.push(device);
+        self
+    }
+
+    pub fn task(mut self, task: Task, enabled: bool) -> GleanConfigBuilder {
+        self.config.tasks.retain(|other| *other != task);
+        if enabled {
+            self.config.tasks.push(task);
+        }
+        self
+    }
+
+    pub fn build(self) -> GleanConfig {
+        self.config
+    }
+}
+
+#[derive(Debug)]
+pub struct NetworkReport {
+    pub explanation: explain::Report,
+    pub plan: Plan,
+    pub files: FileList,
+    // False with noop, and on a reboot of an instance already configured
+    pub written: bool,
+}
+
+#[derive(Debug)]
+pub struct Report {
+    // None unless Task::Network was asked for
+    pub network: Option<NetworkReport>,
+    // The tasks that were due and were done, or would have been with noop
+    pub ran: Vec<Task>,
+}
+
+pub fn run(config: &GleanConfig) -> Result<Report> {
+    let network = if config.runs(Task::Network) {
+        Some(try!(plan_network(config)))
+    } else {
+        None
+    };
+
+    // Only the instance tasks need meta data, so a broken meta_data.json
+    // doesn't stop network config
+    let mut ran = Vec::new();
+    let instance = match config.runs(Task::Hostname) || config.runs(Task::SshKeys) {
+        true => Some(try!(Instance::new(&config.root, try!(MetaData::new(&config.root))))),
+        false => None,
+    };
+    if let Some(ref instance) = instance {
+        if config.runs(Task::Hostname) && instance.should_run(Task::Hostname) {
+            try!(instance.set_hostname(config.noop));
+            ran.push(Task::Hostname);
+        }
+        if config.runs(Task::SshKeys) && instance.should_run(Task::SshKeys) {
+            try!(instance.inject_ssh_keys(config.noop));
+            ran.push(Task::SshKeys);
+        }
+        if !config.noop && !ran.is_empty() {
+            try!(instance.record(&ran));
+        }
+    }
+
+    let (mut report, sys_interfaces, live_networks) = match network {
+        Some(network) => network,
+        None => return Ok(Report { network: None, ran: ran }),
+    };
+    ran.insert(0, Task::Network);
+    if config.noop {
+        return Ok(Report { network: Some(report), ran: ran });
+    }
+    // Without a uuid this run can't be told from a reboot, so config is
+    // written as for a new instance
+    let instance_uuid = match instance {
+        Some(ref instance) => instance.uuid(),
+        None => match MetaData::new(&config.root) {
+            Ok(meta_data) => meta_data.map(|meta_data| meta_data.uuid),
+            Err(why) => {
+                warn!("No instance uuid, writing config anyway: {}", why);
+                None
+            },
+        },
+    };
+    let previous = try!(State::load(&config.root));
+    match previous {
+        Some(ref state) if state.is_reboot(&instance_uuid, &sys_interfaces) => {
+            info!("Reboot of an instance already configured, nothing to do");
+        },
+        _ => {
+            try!(report.files.write());
+            try!(State::new(&previous, &instance_uuid, &sys_interfaces, &live_networks, &report.files).save(&config.root));
+            report.written = true;
+        },
+    }
+    Ok(Report { network: Some(report), ran: ran })
+}
+
+fn plan_network(config: &GleanConfig) -> Result<(NetworkReport, SysInterfaces, LiveNetworks)> {
+    let mut config_drive = match config.dhcp {
+        true => None,
+        false => try!(ConfigDrive::new(&config.root)),
+    };
+    if let Some(ref mut drive) = config_drive {
+        if !config.dns {
+            drive.network.clear_dns();
+        }
+        debug!("Network {:?}", drive.network.devices);
+        debug!("DNS {:?}", drive.network.dns);
+    }
+    let sys_interfaces = try!(SysInterfaces::new(&config.root, &config.interface, config.noop, &config.probe, &config.filter));
+    let mut overrides = match config.dhcp {
+        true => None,
+        false => try!(Overrides::new(&config.root)),
+    };
+    if !config.devices.is_empty() {
+        let kernel_cmdline = Config { devices: config.devices.clone(), dns: Vec::new() };
+        match overrides {
+            Some(ref mut overrides) => overrides.kernel_cmdline = kernel_cmdline,
+            None => overrides = Some(Overrides {
+                network: Config { devices: Vec::new(), dns: Vec::new() },
+                kernel_cmdline: kernel_cmdline,
+            }),
+        }
+    }
+    if let Some(ref overrides) = overrides {
+        debug!("Overrides {:?}", overrides.network.devices);
+        debug!("Kernel command line {:?}", overrides.kernel_cmdline.devices);
+    }
+    let live_networks = try!(LiveNetworks::with_registry(&sys_interfaces, config_drive.clone(), overrides, &config.root, &config.distro, &config.registry));
+    let files = try!(live_networks.get_output());
+    let report = NetworkReport {
+        explanation: explain::Report::new(&sys_interfaces, &config_drive, &live_networks),
+        plan: Plan::new(&sys_interfaces, &live_networks, &files),
+        files: files,
+        written: false,
+    };
+    Ok((report, sys_interfaces, live_networks))
+}
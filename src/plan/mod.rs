@@ -0,0 +1,106 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The planned changes in a form other tools can consume. This is what
+// `glean --output json` prints, so the field names and values here are a
+// stable interface; the schema is documented in README.rst and carries a
+// version number that gets bumped on incompatible changes.
+
+use rustc_serialize::json;
+
+use ::live_networks::{FileList, LiveNetworks, Source};
+use ::sys::SysInterfaces;
+
+static SCHEMA_VERSION: u32 = 1;
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone, PartialEq)]
+pub struct PlannedInterface {
+    pub name: String,
+    pub address: String,
+    pub mode: String,
+    pub source: Option<String>,
+    pub network: Option<String>,
+    // "up", or how long it took when glean brought the link up. Optional
+    // only so that state from before it was recorded still loads.
+    pub carrier: Option<String>,
+}
+
+#[derive(RustcEncodable, Debug)]
+pub struct PlannedFile {
+    pub path: String,
+    pub action: String,
+    pub content: String,
+}
+
+#[derive(RustcEncodable, Debug)]
+pub struct Plan {
+    pub version: u32,
+    pub platform: String,
+    pub interfaces: Vec<PlannedInterface>,
+    pub files: Vec<PlannedFile>,
+}
+
+impl PlannedInterface {
+    pub fn from_live(sys: &SysInterfaces, live: &LiveNetworks) -> Vec<PlannedInterface> {
+        let mut interfaces = Vec::new();
+        for iface in sys.interfaces.iter() {
+            // A source can say dhcp too, or leave a bond slave unaddressed
+            let mode = match live.device(&iface.iface) {
+                Some(device) => device.method.name(),
+                None => "dhcp",
+            };
+            let (source, network) = match live.source(&iface.iface) {
+                Some(&Source::KernelCmdline(ref id)) => (Some("kernel_cmdline"), Some(id.clone())),
+                Some(&Source::Overrides(ref id)) => (Some("overrides"), Some(id.clone())),
+                Some(&Source::ConfigDrive(ref id)) => (Some("config_drive"), Some(id.clone())),
+                Some(&Source::Dhcp) | None => (None, None),
+            };
+            interfaces.push(PlannedInterface {
+                name: iface.iface.clone(),
+                address: iface.address.to_string(),
+                mode: mode.to_string(),
+                source: source.map(|s| s.to_string()),
+                network: network,
+                carrier: Some(iface.carrier.to_string()),
+            });
+        }
+        interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+        return interfaces;
+    }
+}
+
+impl Plan {
+    pub fn new(sys: &SysInterfaces, live: &LiveNetworks, files: &FileList) -> Plan {
+        let mut planned_files = Vec::new();
+        for file in files.files().iter() {
+            planned_files.push(PlannedFile {
+                path: file.path.to_string_lossy().into_owned(),
+                action: file.action.to_string(),
+                content: file.content.clone(),
+            });
+        }
+
+        Plan {
+            version: SCHEMA_VERSION,
+            platform: live.platform_name(),
+            interfaces: PlannedInterface::from_live(sys, live),
+            files: planned_files,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        return json::as_pretty_json(self).to_string();
+    }
+}
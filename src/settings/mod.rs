@@ -0,0 +1,183 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Per-image defaults from /etc/glean/glean.conf under the root, so that
+// policy can be baked into an image rather than into unit files. It's INI:
+//
+//   [glean]
+//   distro = debian            # as --distro
+//   hostname = yes             # set the hostname from meta data
+//   ssh-keys = no              # add root's ssh keys from meta data
+//
+//   [network]
+//   enabled = yes              # write interface config at all
+//   include = driver:virtio_net
+//   exclude = name:docker* bus:usb
+//   carrier-timeout = 10       # as --carrier-timeout
+//   carrier-strategy = events  # as --carrier-strategy
+//   dns = no                   # leave out nameservers from config drive
+//
+// include and exclude take rules as --include and --exclude do, several to
+// a line or over several lines. Booleans are yes/no, true/false, on/off or
+// 1/0. # and ; start comments. Anything left out keeps glean's default, and
+// command line flags win over the file.
+//
+// Keys glean doesn't know are warned about and skipped, since they may be
+// meant for a newer glean. Values it can't make sense of are an error: an
+// exclude rule quietly dropped would configure interfaces that were meant
+// to be left alone.
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ::{Error, Result};
+use ::instance::Task;
+use ::pipeline::GleanConfigBuilder;
+use ::sys::{Rule, Strategy};
+
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    pub distro: Option<String>,
+    pub hostname: Option<bool>,
+    pub ssh_keys: Option<bool>,
+    pub network: Option<bool>,
+    pub include: Vec<Rule>,
+    pub exclude: Vec<Rule>,
+    pub carrier_timeout: Option<u64>,
+    pub carrier_strategy: Option<Strategy>,
+    pub dns: Option<bool>,
+}
+
+impl Settings {
+    // No glean.conf is the same as an empty one
+    pub fn new(root: &Option<String>) -> Result<Settings> {
+        let base_root_path = match root {
+            &Some(ref path) => PathBuf::from(path),
+            &None => PathBuf::from("/"),
+        };
+        let path = base_root_path.join("etc/glean/glean.conf");
+        let mut content = String::new();
+        match File::open(&path).and_then(|mut file| file.read_to_string(&mut content)) {
+            Ok(_) => {},
+            Err(ref why) if why.kind() == ::std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+            Err(why) => return Err(Error::Io(path, why)),
+        }
+        Settings::parse(&content).map_err(|why| Error::InvalidConfig(path.display().to_string(), why))
+    }
+
+    pub fn parse(content: &str) -> ::std::result::Result<Settings, String> {
+        let mut settings = Settings::default();
+        let mut section = String::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                section = line[1..line.len() - 1].trim().to_lowercase();
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(index) => (line[..index].trim().to_lowercase(), line[index + 1..].trim()),
+                None => return Err(format!("line {}: expected key = value", number + 1)),
+            };
+            let result = match (&section[..], &key[..]) {
+                ("glean", "distro") => {
+                    settings.distro = Some(String::from(value));
+                    Ok(())
+                },
+                ("glean", "hostname") => parse_bool(value).map(|flag| settings.hostname = Some(flag)),
+                ("glean", "ssh-keys") => parse_bool(value).map(|flag| settings.ssh_keys = Some(flag)),
+                ("network", "enabled") => parse_bool(value).map(|flag| settings.network = Some(flag)),
+                ("network", "include") => parse_rules(value).map(|rules| settings.include.extend(rules)),
+                ("network", "exclude") => parse_rules(value).map(|rules| settings.exclude.extend(rules)),
+                ("network", "carrier-timeout") => value.parse()
+                    .map(|seconds| settings.carrier_timeout = Some(seconds))
+                    .map_err(|_| format!("'{}' is not a number of seconds", value)),
+                ("network", "carrier-strategy") => match Strategy::from_name(value) {
+                    Some(strategy) => {
+                        settings.carrier_strategy = Some(strategy);
+                        Ok(())
+                    },
+                    None => Err(format!("unknown carrier strategy '{}'", value)),
+                },
+                ("network", "dns") => parse_bool(value).map(|flag| settings.dns = Some(flag)),
+                _ => {
+                    warn!("Ignoring unknown setting {} in [{}]", key, section);
+                    Ok(())
+                },
+            };
+            try!(result.map_err(|why| format!("line {}: {}", number + 1, why)));
+        }
+        Ok(settings)
+    }
+
+    // The file's settings on top of whatever the builder has so far. Apply
+    // command line flags after this so that they win.
+    pub fn apply(&self, mut builder: GleanConfigBuilder) -> GleanConfigBuilder {
+        if let Some(ref distro) = self.distro {
+            builder = builder.distro(distro);
+        }
+        for &(task, enabled) in [(Task::Network, self.network), (Task::Hostname, self.hostname), (Task::SshKeys, self.ssh_keys)].iter() {
+            if let Some(enabled) = enabled {
+                builder = builder.task(task, enabled);
+            }
+        }
+        for rule in self.include.iter() {
+            builder = builder.include(rule.clone());
+        }
+        for rule in self.exclude.iter() {
+            builder = builder.exclude(rule.clone());
+        }
+        if let Some(seconds) = self.carrier_timeout {
+            builder = builder.carrier_timeout(Duration::from_secs(seconds));
+        }
+        if let Some(strategy) = self.carrier_strategy {
+            builder = builder.carrier_strategy(strategy);
+        }
+        if let Some(dns) = self.dns {
+            builder = builder.dns(dns);
+        }
+        builder
+    }
+}
+
+// Comments run from # or ; at the start of a line or after whitespace
+fn strip_comment(line: &str) -> &str {
+    let mut previous = ' ';
+    for (index, c) in line.char_indices() {
+        if (c == '#' || c == ';') && previous.is_whitespace() {
+            return &line[..index];
+        }
+        previous = c;
+    }
+    line
+}
+
+fn parse_bool(value: &str) -> ::std::result::Result<bool, String> {
+    match &value.to_lowercase()[..] {
+        "yes" | "true" | "on" | "1" => Ok(true),
+        "no" | "false" | "off" | "0" => Ok(false),
+        _ => Err(format!("'{}' is not yes or no", value)),
+    }
+}
+
+fn parse_rules(value: &str) -> ::std::result::Result<Vec<Rule>, String> {
+    value.split_whitespace()
+        .map(|rule| Rule::from_str(rule).ok_or(format!("invalid interface rule '{}'", rule)))
+        .collect()
+}
@@ -0,0 +1,204 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// A record of what glean did, kept in /var/lib/glean/state.json. It serves
+// as an audit trail and lets a reboot of an instance we've already
+// configured be told apart from the first boot of a new one. It is also
+// where the per-instance tasks done for the instance are kept.
+
+use rustc_serialize::json;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ::{Error, Result};
+use ::checksum::sha256;
+use ::instance::Task;
+use ::live_networks::{Action, FileList, LiveNetworks};
+use ::plan::PlannedInterface;
+use ::sys::{HardwareAddress, SysInterfaces};
+
+static SCHEMA_VERSION: u32 = 1;
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone)]
+pub struct WrittenFile {
+    pub path: String,
+    pub sha256: String,
+}
+
+#[derive(RustcEncodable, RustcDecodable, Debug, Clone)]
+pub struct State {
+    pub version: u32,
+    pub instance_uuid: Option<String>,
+    // Names of the tasks done for that instance. network is only there
+    // once its config has been written.
+    pub tasks: Vec<String>,
+    // Seconds since the epoch
+    pub timestamp: u64,
+    pub platform: String,
+    pub interfaces: Vec<PlannedInterface>,
+    pub files: Vec<WrittenFile>,
+}
+
+fn state_path(root: &Option<String>) -> PathBuf {
+    let base_root_path = match root {
+        &Some(ref path) => PathBuf::from(path),
+        &None => PathBuf::from("/"),
+    };
+    return base_root_path.join("var/lib/glean/state.json");
+}
+
+fn now() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+impl State {
+    // After writing network config. Tasks done earlier for the same
+    // instance are kept from the previous state.
+    pub fn new(previous: &Option<State>, instance_uuid: &Option<String>, sys: &SysInterfaces, live: &LiveNetworks, files: &FileList) -> State {
+        let mut written = Vec::new();
+        for file in files.files().iter() {
+            if file.action == Action::Create || file.action == Action::Replace {
+                written.push(WrittenFile {
+                    path: file.path.to_string_lossy().into_owned(),
+                    sha256: sha256(file.content.as_bytes()),
+                });
+            }
+        }
+        let mut state = State {
+            version: SCHEMA_VERSION,
+            instance_uuid: None,
+            tasks: Vec::new(),
+            timestamp: now(),
+            platform: live.platform_name(),
+            interfaces: PlannedInterface::from_live(sys, live),
+            files: written,
+        };
+        if let &Some(ref previous) = previous {
+            state.instance_uuid = previous.instance_uuid.clone();
+            state.tasks = previous.tasks.clone();
+        }
+        state.record(instance_uuid, &[Task::Network]);
+        state
+    }
+
+    // Before glean has written any network config
+    pub fn empty() -> State {
+        State {
+            version: SCHEMA_VERSION,
+            instance_uuid: None,
+            tasks: Vec::new(),
+            timestamp: now(),
+            platform: String::new(),
+            interfaces: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    // Notes tasks as done for an instance. Those done for another instance
+    // are forgotten.
+    pub fn record(&mut self, instance_uuid: &Option<String>, done: &[Task]) {
+        if self.instance_uuid != *instance_uuid {
+            self.instance_uuid = instance_uuid.clone();
+            self.tasks.clear();
+        }
+        for task in done.iter() {
+            if !self.tasks.iter().any(|name| name == task.name()) {
+                self.tasks.push(String::from(task.name()));
+            }
+        }
+        self.timestamp = now();
+    }
+
+    pub fn has_done(&self, instance_uuid: &Option<String>, task: Task) -> bool {
+        instance_uuid.is_some() && self.instance_uuid == *instance_uuid
+            && self.tasks.iter().any(|name| name == task.name())
+    }
+
+    // Ok(None) if glean has never recorded a run under this root
+    pub fn load(root: &Option<String>) -> Result<Option<State>> {
+        let path = state_path(root);
+        let mut json_string = String::new();
+        match File::open(&path).and_then(|mut f| f.read_to_string(&mut json_string)) {
+            Ok(_) => {},
+            Err(ref why) if why.kind() == ::std::io::ErrorKind::NotFound => return Ok(None),
+            Err(why) => return Err(Error::Io(path, why)),
+        };
+        match json::decode::<State>(&json_string) {
+            Ok(state) => Ok(Some(state)),
+            Err(why) => Err(Error::Decode(path, why.to_string())),
+        }
+    }
+
+    pub fn save(&self, root: &Option<String>) -> Result<()> {
+        let path = state_path(root);
+        if let Some(parent) = path.parent() {
+            try!(fs::create_dir_all(parent).map_err(|why| Error::Io(parent.to_path_buf(), why)));
+        }
+        let mut file = try!(File::create(&path).map_err(|why| Error::Io(path.clone(), why)));
+        try!(write!(file, "{}\n", json::as_pretty_json(self)).map_err(|why| Error::Io(path.clone(), why)));
+        Ok(())
+    }
+
+    // A reboot of the instance we last wrote network config for, on the
+    // same hardware, needs nothing doing. Without an instance uuid we can't
+    // tell.
+    pub fn is_reboot(&self, instance_uuid: &Option<String>, sys: &SysInterfaces) -> bool {
+        if !self.has_done(instance_uuid, Task::Network) {
+            return false;
+        }
+        let mut recorded: Vec<(&String, Option<HardwareAddress>)> = self.interfaces.iter()
+            .map(|iface| (&iface.name, HardwareAddress::parse(&iface.address))).collect();
+        let mut current: Vec<(&String, Option<HardwareAddress>)> = sys.interfaces.iter()
+            .map(|iface| (&iface.iface, Some(iface.address.clone()))).collect();
+        recorded.sort();
+        current.sort();
+        return recorded == current;
+    }
+
+    pub fn to_json(&self) -> String {
+        return json::as_pretty_json(self).to_string();
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.instance_uuid {
+            Some(ref uuid) => try!(writeln!(f, "instance: {}", uuid)),
+            None => try!(writeln!(f, "instance: unknown")),
+        }
+        try!(writeln!(f, "tasks done: {}", self.tasks.join(" ")));
+        try!(writeln!(f, "last run: {} (seconds since epoch)", self.timestamp));
+        try!(writeln!(f, "platform: {}", self.platform));
+        try!(writeln!(f, "interfaces:"));
+        for iface in self.interfaces.iter() {
+            match (&iface.source, &iface.network) {
+                (&Some(ref source), &Some(ref network)) =>
+                    try!(writeln!(f, "    {} {} {} from {} network {}", iface.name, iface.address, iface.mode, source, network)),
+                _ => try!(writeln!(f, "    {} {} {}", iface.name, iface.address, iface.mode)),
+            }
+        }
+        try!(writeln!(f, "files:"));
+        for file in self.files.iter() {
+            try!(writeln!(f, "    {}  {}", file.sha256, file.path));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp;
+use std::fmt;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use ::{Error, Result};
+use super::get_file_value;
+use super::netlink::LinkSocket;
+
+// How to find out that a link we've brought up has carrier
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    // Wait for the kernel to tell us over netlink
+    Events,
+    // Re-read carrier from sysfs, backing off between reads
+    Poll,
+    // Don't bring links up at all, only take ones that already have carrier
+    None,
+}
+
+impl Strategy {
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        match name {
+            "events" => Some(Strategy::Events),
+            "poll" => Some(Strategy::Poll),
+            "none" => Some(Strategy::None),
+            _ => None,
+        }
+    }
+}
+
+// What looking for carrier on a link found
+#[derive(Debug, Clone, PartialEq)]
+pub enum Carrier {
+    // Had it before we did anything
+    Up,
+    // Came up this long after we brought the link up
+    CameUp(Duration),
+    // Still none when the timeout ran out
+    TimedOut(Duration),
+    // None, and the link was left down in noop or with Strategy::None
+    NotProbed,
+    // The link couldn't be brought up to find out
+    Failed(String),
+}
+
+impl Carrier {
+    pub fn is_up(&self) -> bool {
+        match *self {
+            Carrier::Up | Carrier::CameUp(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Carrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Carrier::Up => write!(f, "up"),
+            Carrier::CameUp(after) => write!(f, "came up after {}ms", millis(after)),
+            Carrier::TimedOut(timeout) => write!(f, "no carrier within {}ms", millis(timeout)),
+            Carrier::NotProbed => write!(f, "no carrier, link not brought up"),
+            Carrier::Failed(ref why) => write!(f, "couldn't bring the link up: {}", why),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CarrierProbe {
+    pub timeout: Duration,
+    pub strategy: Strategy,
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1000000) as u64
+}
+
+fn has_carrier(path: &PathBuf) -> bool {
+    match get_file_value(&path.join("carrier")) {
+        Ok(value) => value == "1",
+        Err(_) => false,
+    }
+}
+
+fn poll_carrier(path: &PathBuf, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut delay = Duration::from_millis(100);
+    loop {
+        if has_carrier(path) {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        thread::sleep(cmp::min(delay, deadline - now));
+        delay = cmp::min(delay * 2, Duration::from_secs(2));
+    }
+}
+
+impl CarrierProbe {
+    pub fn new(timeout: Duration, strategy: Strategy) -> CarrierProbe {
+        CarrierProbe {
+            timeout: timeout,
+            strategy: strategy,
+        }
+    }
+
+    pub fn probe(&self, path: &PathBuf, noop: bool) -> Carrier {
+        // Check to see if it's a live interface
+        if has_carrier(path) {
+            return Carrier::Up;
+        }
+        // Bringing the link up is a change to the system, so noop stops here
+        if noop || self.strategy == Strategy::None {
+            return Carrier::NotProbed;
+        }
+        let start = Instant::now();
+        let carrier = match self.bring_up(path) {
+            Ok(true) => Carrier::CameUp(start.elapsed()),
+            Ok(false) => Carrier::TimedOut(self.timeout),
+            // Not the same as no carrier, the link may be fine
+            Err(why) => {
+                warn!("Couldn't bring up {}: {}", path.display(), why);
+                Carrier::Failed(why.to_string())
+            },
+        };
+        info!("{}: {}", path.display(), carrier);
+        return carrier;
+    }
+
+    fn bring_up(&self, path: &PathBuf) -> Result<bool> {
+        let index = match try!(get_file_value(&path.join("ifindex"))).parse::<i32>() {
+            Ok(index) => index,
+            Err(why) => return Err(Error::Decode(path.join("ifindex"), why.to_string())),
+        };
+        let netlink_error = |why| Error::Io(path.clone(), why);
+        let mut socket = try!(LinkSocket::new().map_err(&netlink_error));
+        debug!("Setting {} up", path.display());
+        try!(socket.set_up(index).map_err(&netlink_error));
+        // Carrier may have come up before we were listening
+        if has_carrier(path) {
+            return Ok(true);
+        }
+        match self.strategy {
+            Strategy::Events => socket.wait_for_carrier(index, self.timeout).map_err(&netlink_error),
+            _ => Ok(poll_carrier(path, self.timeout)),
+        }
+    }
+}
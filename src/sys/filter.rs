@@ -0,0 +1,138 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Include and exclude rules for interfaces, matched against what sysfs says
+// about them. Rules are written as `kind:value`, or just `kind` for the
+// ones that are a yes or no question:
+//
+//   name:GLOB         interface name, e.g. name:docker*
+//   type:TYPE         link type, a number from /sys/class/net/*/type or
+//                     ether, infiniband or loopback
+//   driver:DRIVER     the device/driver the interface is bound to
+//   bus:SUBSYSTEM     the device/subsystem, e.g. pci, usb or vmbus
+//   wireless          has wireless extensions
+//   bridge            is a bridge
+//   bond              is a bonding master
+
+use glob::Pattern;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use super::get_file_value;
+
+#[derive(Debug, Clone)]
+pub enum Rule {
+    Name(Pattern),
+    Type(String),
+    Driver(String),
+    Bus(String),
+    Wireless,
+    Bridge,
+    Bond,
+}
+
+impl Rule {
+    pub fn from_str(rule: &str) -> Option<Rule> {
+        let (kind, value) = match rule.find(':') {
+            Some(index) => (&rule[..index], Some(&rule[index + 1..])),
+            None => (rule, None),
+        };
+        match (kind, value) {
+            ("name", Some(glob)) => Pattern::new(glob).ok().map(Rule::Name),
+            ("type", Some(link_type)) => Some(Rule::Type(String::from(match link_type {
+                "ether" => "1",
+                "infiniband" => "32",
+                "loopback" => "772",
+                other => other,
+            }))),
+            ("driver", Some(driver)) => Some(Rule::Driver(String::from(driver))),
+            ("bus", Some(bus)) => Some(Rule::Bus(String::from(bus))),
+            ("wireless", None) => Some(Rule::Wireless),
+            ("bridge", None) => Some(Rule::Bridge),
+            ("bond", None) => Some(Rule::Bond),
+            _ => None,
+        }
+    }
+
+    pub fn matches(&self, path: &PathBuf) -> bool {
+        let iface = path.file_name().unwrap().to_string_lossy().into_owned();
+        match *self {
+            Rule::Name(ref pattern) => pattern.matches(&iface),
+            Rule::Type(ref link_type) => match get_file_value(&path.join("type")) {
+                Ok(value) => value == *link_type,
+                Err(_) => false,
+            },
+            Rule::Driver(ref driver) => link_name(&path.join("device/driver")) == Some(driver.clone()),
+            Rule::Bus(ref bus) => link_name(&path.join("device/subsystem")) == Some(bus.clone()),
+            Rule::Wireless => path.join("wireless").exists() || path.join("phy80211").exists(),
+            Rule::Bridge => path.join("bridge").exists(),
+            Rule::Bond => path.join("bonding").exists(),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Rule::Name(ref pattern) => write!(f, "name:{}", pattern),
+            Rule::Type(ref link_type) => write!(f, "type:{}", link_type),
+            Rule::Driver(ref driver) => write!(f, "driver:{}", driver),
+            Rule::Bus(ref bus) => write!(f, "bus:{}", bus),
+            Rule::Wireless => write!(f, "wireless"),
+            Rule::Bridge => write!(f, "bridge"),
+            Rule::Bond => write!(f, "bond"),
+        }
+    }
+}
+
+// The last component of where a sysfs symlink points, which is how the
+// kernel names drivers and buses
+fn link_name(path: &PathBuf) -> Option<String> {
+    match fs::read_link(path) {
+        Ok(target) => target.file_name().map(|name| name.to_string_lossy().into_owned()),
+        Err(_) => None,
+    }
+}
+
+// With no include rules every interface is a candidate. Exclude rules win
+// over include rules.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    pub include: Vec<Rule>,
+    pub exclude: Vec<Rule>,
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter {
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    // Err says which rule turned the interface down
+    pub fn check(&self, path: &PathBuf) -> Result<(), String> {
+        for rule in self.exclude.iter() {
+            if rule.matches(path) {
+                return Err(format!("excluded by {}", rule));
+            }
+        }
+        if self.include.is_empty() || self.include.iter().any(|rule| rule.matches(path)) {
+            return Ok(());
+        }
+        return Err(String::from("not matched by any include rule"));
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Link-layer addresses as read from /sys/class/net/*/address or given as
+// ethernet_mac_address in network_info.json. Ethernet addresses are 6 bytes.
+// IPoIB addresses are 20: 4 bytes of flags and queue pair number, which
+// change from boot to boot, then the 16 byte GID whose last 8 bytes are the
+// port GUID. Nova only knows about the GUID, so that is what gets compared.
+
+use std::fmt;
+
+const ETHERNET_LEN: usize = 6;
+const INFINIBAND_LEN: usize = 20;
+const GUID_LEN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct HardwareAddress {
+    bytes: Vec<u8>,
+}
+
+impl HardwareAddress {
+    // Accepts hex digits in either case, in pairs separated by ':' or '-',
+    // in groups of four separated by '.', or with no separators at all
+    pub fn parse(value: &str) -> Option<HardwareAddress> {
+        let digits: Vec<char> = value.trim().chars()
+            .filter(|c| *c != ':' && *c != '-' && *c != '.')
+            .collect();
+        if digits.is_empty() || digits.len() % 2 != 0 {
+            return None;
+        }
+        let mut bytes = Vec::new();
+        for pair in digits.chunks(2) {
+            let high = match pair[0].to_digit(16) {
+                Some(digit) => digit,
+                None => return None,
+            };
+            let low = match pair[1].to_digit(16) {
+                Some(digit) => digit,
+                None => return None,
+            };
+            bytes.push((high * 16 + low) as u8);
+        }
+        Some(HardwareAddress { bytes: bytes })
+    }
+
+    pub fn is_ethernet(&self) -> bool {
+        self.bytes.len() == ETHERNET_LEN
+    }
+
+    pub fn is_infiniband(&self) -> bool {
+        self.bytes.len() == INFINIBAND_LEN
+    }
+
+    // The port GUID of an IPoIB address, or an address that is only a GUID
+    pub fn guid(&self) -> Option<&[u8]> {
+        match self.bytes.len() {
+            INFINIBAND_LEN => Some(&self.bytes[INFINIBAND_LEN - GUID_LEN..]),
+            GUID_LEN => Some(&self.bytes[..]),
+            _ => None,
+        }
+    }
+
+    // Whether two spellings of an address refer to the same port. Ironic
+    // sometimes hands out an Ethernet style MAC for an IB port, made by
+    // dropping the middle two bytes of the GUID, so that is accepted too.
+    pub fn matches(&self, other: &HardwareAddress) -> bool {
+        if self.bytes == other.bytes {
+            return true;
+        }
+        match (self.guid(), other.guid()) {
+            (Some(guid), Some(other_guid)) => return guid == other_guid,
+            (Some(guid), None) if other.is_ethernet() => return mac_from_guid(guid) == other.bytes,
+            (None, Some(other_guid)) if self.is_ethernet() => return mac_from_guid(other_guid) == self.bytes,
+            _ => {},
+        }
+        false
+    }
+}
+
+fn mac_from_guid(guid: &[u8]) -> Vec<u8> {
+    vec![guid[0], guid[1], guid[2], guid[5], guid[6], guid[7]]
+}
+
+impl fmt::Display for HardwareAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let octets: Vec<String> = self.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        write!(f, "{}", octets.join(":"))
+    }
+}
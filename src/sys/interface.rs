@@ -13,102 +13,103 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::fs::File;
-use std::io::prelude::*;
+use std::fmt;
 use std::path::PathBuf;
-use std::process::Command;
+use std::result;
 use std::str::FromStr;
 
+use ::Result;
+use super::carrier::{Carrier, CarrierProbe};
+use super::filter::Filter;
+use super::hardware_address::HardwareAddress;
+use super::get_file_value;
+
 #[derive(Debug, Clone)]
 pub struct Interface {
-    pub address: String,
+    pub address: HardwareAddress,
     pub iface: String,
+    pub carrier: Carrier,
+}
+
+// Why an interface in /sys/class/net is not one we configure
+#[derive(Debug, Clone)]
+pub enum Reason {
+    Loopback,
+    Filtered(String),
+    AddrAssignType(String),
+    NoCarrier(Carrier),
+    BadAddress(String),
+    Unreadable(String),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Reason::Loopback => write!(f, "loopback"),
+            Reason::Filtered(ref why) => write!(f, "{}", why),
+            Reason::AddrAssignType(ref value) =>
+                write!(f, "addr_assign_type is {}, not a permanent address", value),
+            Reason::NoCarrier(ref carrier) => write!(f, "{}", carrier),
+            Reason::BadAddress(ref value) => write!(f, "unusable hardware address '{}'", value),
+            Reason::Unreadable(ref why) => write!(f, "unreadable: {}", why),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Rejected {
+    pub iface: String,
+    pub address: Option<HardwareAddress>,
+    pub reason: Reason,
 }
 
 impl Interface {
-  pub fn new(path: &PathBuf, noop: bool) -> Option<Interface> {
-      let iface = path.as_path().file_name().unwrap();
-      if iface == "lo" {
-          return None;
+  // Ok(None) means the interface is there but not one we should configure
+  pub fn new(path: &PathBuf, noop: bool, probe: &CarrierProbe, filter: &Filter) -> Result<Option<Interface>> {
+      match try!(Interface::examine(path, noop, probe, filter)) {
+          Ok(interface) => Ok(Some(interface)),
+          Err(_) => Ok(None),
       }
-      let assign_type = match get_file_value(&path.join("addr_assign_type")) {
-        None => return None,
-        Some(value) => value,
+  }
+
+  // Like new, but says why an interface was turned down
+  pub fn examine(path: &PathBuf, noop: bool, probe: &CarrierProbe, filter: &Filter) -> Result<result::Result<Interface, Rejected>> {
+      let iface = String::from_str(path.as_path().file_name().unwrap().to_str().unwrap()).unwrap();
+      let reject = |reason| {
+          Ok(Err(Rejected {
+              iface: iface.clone(),
+              address: get_file_value(&path.join("address")).ok()
+                  .and_then(|value| HardwareAddress::parse(&value)),
+              reason: reason,
+          }))
       };
+      if iface == "lo" {
+          return reject(Reason::Loopback);
+      }
+      match filter.check(&path) {
+          Ok(()) => {},
+          Err(why) => return reject(Reason::Filtered(why)),
+      }
+      let assign_type = try!(get_file_value(&path.join("addr_assign_type")));
       if assign_type != "0" {
-          return None
+          return reject(Reason::AddrAssignType(assign_type));
       }
 
-      if ! is_interface_live(&path, noop) {
-          return None
+      let carrier = probe.probe(&path, noop);
+      if !carrier.is_up() {
+          return reject(Reason::NoCarrier(carrier));
       }
 
-      let address = match get_file_value(&path.join("address")) {
-        None => return None,
-        Some(value) => value,
+      let raw_address = try!(get_file_value(&path.join("address")));
+      let address = match HardwareAddress::parse(&raw_address) {
+          Some(address) => address,
+          None => return reject(Reason::BadAddress(raw_address)),
       };
 
-      Some(Interface {
+      Ok(Ok(Interface {
          address: address,
-         iface: String::from_str(iface.to_str().unwrap()).unwrap(),
-      })
-  }
-}
-
-fn get_file_value(path: &PathBuf) -> Option<String> {
-  let newline = '\n';
-  let mut file_obj = match File::open(path) {
-    Err(why) => { debug!("{}", why); return None},
-    Ok(file) => file,
-  };
-  let mut raw_value = String::new();
-  match file_obj.read_to_string(&mut raw_value) {
-    Err(why) => { debug!("{}", why); return None},
-    Ok(_) => {},
-  };
-  let value = raw_value.trim_matches(newline);
-  debug!("File {} value is: ::{}::", path.display(), value);
-  return Some(String::from_str(value).unwrap());
-}
-
-fn is_interface_live(path: &PathBuf, noop: bool) -> bool {
-  // Check to see if it's a live interface
-  match get_file_value(&path.join("carrier")) {
-    None => {}
-    Some(value) => {
-        if value == "1" {
-            return true;
-        }
-    },
-  };
-  // This will be there so do things with ip link set dev {} up
-  if noop {
-      return false;
-  } else {
-      let iface = path.as_path().file_name().unwrap();
-      let mut range = 0..10;
-      loop {
-          match range.next() {
-              Some(x) => {
-                  debug!("ip link {:?} number {}", iface, x);
-                  match Command::new("ip")
-                      .arg("link").arg("set").arg("dev").arg(iface).arg("up")
-                      .output() {
-                    Err(why) => debug!("ip link set error: {}", why),
-                    _ => {},
-                  };
-
-                  match get_file_value(&path.join("carrier")) {
-                    None => {}
-                    Some(value) => {
-                        if value == "1" {
-                            return true;
-                        }
-                    },
-                  };
-              },
-              None => return false,
-          }
-      }
+         iface: iface.clone(),
+         carrier: carrier,
+      }))
   }
 }
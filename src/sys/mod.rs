@@ -14,18 +14,41 @@
 // limitations under the License.
 
 use glob::glob;
+use std::cmp;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
+use ::{Error, Result};
+
+pub use self::carrier::{Carrier, CarrierProbe, Strategy};
+pub use self::filter::{Filter, Rule};
+pub use self::hardware_address::HardwareAddress;
+pub use self::interface::{Interface, Reason, Rejected};
+
+mod carrier;
+mod filter;
+mod hardware_address;
 mod interface;
+mod netlink;
+
+// Hosts can have hundreds of vlans and veths in sysfs, so only this many
+// interfaces are probed at once
+const MAX_PROBES: usize = 16;
 
 #[derive(Debug, Clone)]
 pub struct SysInterfaces {
     root: PathBuf,
     pub interfaces: Vec<interface::Interface>,
+    pub rejected: Vec<interface::Rejected>,
 }
 
 impl SysInterfaces {
-  pub fn new(root: &Option<String>, interface: &Option<String>, noop: bool) -> SysInterfaces {
+  pub fn new(root: &Option<String>, interface: &Option<String>, noop: bool, probe: &CarrierProbe, filter: &Filter) -> Result<SysInterfaces> {
 
       let base_root_path = match root {
           &Some(ref path) => PathBuf::from(path),
@@ -33,27 +56,95 @@ impl SysInterfaces {
       };
       let root_path = base_root_path.join("sys/class/net");
       debug!("Root Path {:?}", root_path);
-      SysInterfaces {
+      let (interfaces, rejected) = try!(get_interfaces(&root_path, interface, noop, probe, filter));
+      Ok(SysInterfaces {
           root: PathBuf::from(&root_path),
-          interfaces: get_interfaces(&root_path, interface, noop),
+          interfaces: interfaces,
+          rejected: rejected,
+      })
+  }
+
+  // Whether the kernel knows about the interface at all, configurable or not
+  pub fn exists(&self, iface: &String) -> bool {
+      self.root.join(iface).exists()
+  }
+
+  // False when sysfs lists nothing at all, as in a chroot without /sys
+  // mounted, where no interface can be said to be missing
+  pub fn is_available(&self) -> bool {
+      match fs::read_dir(&self.root) {
+          Ok(mut entries) => entries.next().is_some(),
+          Err(_) => false,
       }
   }
 }
 
-fn get_interfaces(root_path: &PathBuf, interface: &Option<String>, noop: bool) -> Vec<interface::Interface> {
+fn get_interfaces(root_path: &PathBuf, interface: &Option<String>, noop: bool, probe: &CarrierProbe, filter: &Filter) -> Result<(Vec<interface::Interface>, Vec<interface::Rejected>)> {
+    // An interface asked for by name has to be readable. When scanning
+    // everything, one odd entry in sysfs shouldn't stop the rest.
     let interface_paths = match interface {
-        &Some(ref iface) => vec![root_path.join(iface)],
+        &Some(ref iface) => {
+            return match try!(interface::Interface::examine(&root_path.join(iface), noop, probe, filter)) {
+                Ok(iface) => Ok((vec![iface], Vec::new())),
+                Err(rejected) => Ok((Vec::new(), vec![rejected])),
+            };
+        },
         &None => read_interfaces(root_path),
     };
+    // Waiting on carrier can take as long as the switch port does, so
+    // interfaces are probed side by side by a pool of workers rather than one
+    // after another
+    let workers = cmp::min(MAX_PROBES, interface_paths.len());
+    let queue = Arc::new(Mutex::new(interface_paths.into_iter().enumerate().collect::<Vec<_>>()));
+    let (sender, receiver) = mpsc::channel();
+    let handles: Vec<_> = (0..workers).map(|_| {
+        let queue = queue.clone();
+        let sender = sender.clone();
+        let probe = probe.clone();
+        let filter = filter.clone();
+        thread::spawn(move || loop {
+            let next = match queue.lock() {
+                Ok(mut queue) => queue.pop(),
+                Err(_) => None,
+            };
+            let (index, path) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            match panic::catch_unwind(AssertUnwindSafe(|| interface::Interface::examine(&path, noop, &probe, &filter))) {
+                Ok(interface) => { let _ = sender.send((index, path, interface)); },
+                Err(_) => warn!("Probing {} panicked", path.display()),
+            }
+        })
+    }).collect();
+    drop(sender);
+    // Back in sysfs order, however long each probe took
+    let mut results: Vec<_> = receiver.iter().collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
     let mut interfaces = Vec::new();
-    for path in interface_paths {
-        let interface = interface::Interface::new(&path, noop);
+    let mut rejected = Vec::new();
+    for (_, path, interface) in results {
         match interface {
-            None => {}
-            Some(iface) => interfaces.push(iface),
+            Err(why) => {
+                warn!("Skipping {}: {}", path.display(), why);
+                rejected.push(interface::Rejected {
+                    iface: path.file_name().unwrap().to_string_lossy().into_owned(),
+                    address: None,
+                    reason: interface::Reason::Unreadable(why.to_string()),
+                });
+            },
+            Ok(Err(iface)) => {
+                debug!("Skipping {}: {}", iface.iface, iface.reason);
+                rejected.push(iface);
+            },
+            Ok(Ok(iface)) => interfaces.push(iface),
         }
     }
-    return interfaces;
+    return Ok((interfaces, rejected));
 }
 
 fn read_interfaces(root_path: &PathBuf) -> Vec<PathBuf> {
@@ -68,3 +159,19 @@ fn read_interfaces(root_path: &PathBuf) -> Vec<PathBuf> {
    }
    return interfaces;
 }
+
+fn get_file_value(path: &PathBuf) -> Result<String> {
+  let newline = '\n';
+  let mut file_obj = match File::open(path) {
+    Err(why) => { debug!("{}", why); return Err(Error::Io(path.clone(), why)) },
+    Ok(file) => file,
+  };
+  let mut raw_value = String::new();
+  match file_obj.read_to_string(&mut raw_value) {
+    Err(why) => { debug!("{}", why); return Err(Error::Io(path.clone(), why)) },
+    Ok(_) => {},
+  };
+  let value = raw_value.trim_matches(newline);
+  debug!("File {} value is: ::{}::", path.display(), value);
+  return Ok(String::from_str(value).unwrap());
+}
@@ -0,0 +1,261 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Just enough rtnetlink to bring a link up and wait to hear that it has
+// carrier, without needing iproute2 around. The kernel structures are laid
+// out by hand since all we need is the link header.
+
+use libc;
+use std::io;
+use std::mem;
+use std::slice;
+use std::time::{Duration, Instant};
+
+// Not in every libc 0.2 release, so spelled out here like the rest
+const AF_UNSPEC: u8 = 0;
+const MSG_TRUNC: libc::c_int = 0x20;
+
+const NETLINK_ROUTE: libc::c_int = 0;
+const RTMGRP_LINK: u32 = 1;
+
+const NLMSG_ERROR: u16 = 2;
+const RTM_NEWLINK: u16 = 16;
+
+const NLM_F_REQUEST: u16 = 1;
+const NLM_F_ACK: u16 = 4;
+
+const IFF_UP: u32 = 0x1;
+const IFF_LOWER_UP: u32 = 0x10000;
+
+// The kernel acks straight away, so not hearing back means something is
+// badly wrong and waiting longer won't help
+const ACK_TIMEOUT_SECS: u64 = 5;
+
+#[repr(C)]
+struct SockaddrNl {
+    nl_family: libc::sa_family_t,
+    nl_pad: u16,
+    nl_pid: u32,
+    nl_groups: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct NlMsgHdr {
+    nlmsg_len: u32,
+    nlmsg_type: u16,
+    nlmsg_flags: u16,
+    nlmsg_seq: u32,
+    nlmsg_pid: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct IfInfoMsg {
+    ifi_family: u8,
+    ifi_pad: u8,
+    ifi_type: u16,
+    ifi_index: i32,
+    ifi_flags: u32,
+    ifi_change: u32,
+}
+
+#[repr(C)]
+struct SetLinkRequest {
+    header: NlMsgHdr,
+    info: IfInfoMsg,
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+pub struct LinkSocket {
+    fd: libc::c_int,
+    // Kept as u32s so that every message in it is suitably aligned
+    buffer: Vec<u32>,
+}
+
+impl LinkSocket {
+    // Subscribes to link events straight away, so that none are missed
+    // between bringing a link up and waiting on it
+    pub fn new() -> io::Result<LinkSocket> {
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, NETLINK_ROUTE)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = LinkSocket { fd: fd, buffer: vec![0; 2048] };
+        let address = SockaddrNl {
+            nl_family: libc::AF_NETLINK as libc::sa_family_t,
+            nl_pad: 0,
+            nl_pid: 0,
+            nl_groups: RTMGRP_LINK,
+        };
+        let ret = unsafe {
+            libc::bind(fd, &address as *const SockaddrNl as *const libc::sockaddr,
+                       mem::size_of::<SockaddrNl>() as libc::socklen_t)
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(socket)
+    }
+
+    pub fn set_up(&mut self, index: i32) -> io::Result<()> {
+        let seq = 1;
+        let request = SetLinkRequest {
+            header: NlMsgHdr {
+                nlmsg_len: mem::size_of::<SetLinkRequest>() as u32,
+                nlmsg_type: RTM_NEWLINK,
+                nlmsg_flags: NLM_F_REQUEST | NLM_F_ACK,
+                nlmsg_seq: seq,
+                nlmsg_pid: 0,
+            },
+            info: IfInfoMsg {
+                ifi_family: AF_UNSPEC,
+                ifi_pad: 0,
+                ifi_type: 0,
+                ifi_index: index,
+                ifi_flags: IFF_UP,
+                ifi_change: IFF_UP,
+            },
+        };
+        let ret = unsafe {
+            libc::send(self.fd, &request as *const SetLinkRequest as *const libc::c_void,
+                       mem::size_of::<SetLinkRequest>(), 0)
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Link events may arrive ahead of our ack, skip past them
+        let deadline = Instant::now() + Duration::from_secs(ACK_TIMEOUT_SECS);
+        loop {
+            if !try!(self.wait_readable(deadline)) {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "no ack from the kernel"));
+            }
+            let len = try!(self.receive());
+            for (header, offset) in self.messages(len) {
+                let payload = header.nlmsg_len as usize - mem::size_of::<NlMsgHdr>();
+                if header.nlmsg_type == NLMSG_ERROR && header.nlmsg_seq == seq && payload >= mem::size_of::<i32>() {
+                    let error = self.read::<i32>(offset + mem::size_of::<NlMsgHdr>());
+                    if error == 0 {
+                        return Ok(());
+                    }
+                    return Err(io::Error::from_raw_os_error(-error));
+                }
+            }
+        }
+    }
+
+    // True if the link reported carrier before the timeout ran out
+    pub fn wait_for_carrier(&mut self, index: i32, timeout: Duration) -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if !try!(self.wait_readable(deadline)) {
+                return Ok(false);
+            }
+            let len = try!(self.receive());
+            for (header, offset) in self.messages(len) {
+                let payload = header.nlmsg_len as usize - mem::size_of::<NlMsgHdr>();
+                if header.nlmsg_type != RTM_NEWLINK || payload < mem::size_of::<IfInfoMsg>() {
+                    continue;
+                }
+                let info = self.read::<IfInfoMsg>(offset + mem::size_of::<NlMsgHdr>());
+                if info.ifi_index == index && info.ifi_flags & IFF_LOWER_UP != 0 {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    // False if there was still nothing to read by the deadline
+    fn wait_readable(&self, deadline: Instant) -> io::Result<bool> {
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(false);
+            }
+            let remaining = deadline - now;
+            let millis = remaining.as_secs() * 1000 + (remaining.subsec_nanos() / 1000000) as u64 + 1;
+            let mut poll_fd = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+            let ready = unsafe { libc::poll(&mut poll_fd, 1, millis as libc::c_int) };
+            if ready < 0 {
+                let why = io::Error::last_os_error();
+                if why.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(why);
+            }
+            if ready > 0 {
+                return Ok(true);
+            }
+        }
+    }
+
+    // With MSG_TRUNC the kernel says how long the message really was, so a
+    // short read is an error rather than messages silently lost off the end
+    fn receive(&mut self) -> io::Result<usize> {
+        let capacity = self.buffer.len() * mem::size_of::<u32>();
+        let ret = unsafe {
+            libc::recv(self.fd, self.buffer.as_mut_ptr() as *mut libc::c_void, capacity, MSG_TRUNC)
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if ret as usize > capacity {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      format!("netlink message of {} bytes truncated to {}", ret, capacity)));
+        }
+        Ok(ret as usize)
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.buffer.as_ptr() as *const u8,
+                                  self.buffer.len() * mem::size_of::<u32>())
+        }
+    }
+
+    // offset must be 4 byte aligned, which netlink guarantees for the
+    // start of each message and of the payload after its header
+    fn read<T: Copy>(&self, offset: usize) -> T {
+        assert!(offset % 4 == 0 && offset + mem::size_of::<T>() <= self.bytes().len());
+        unsafe { *(self.bytes()[offset..].as_ptr() as *const T) }
+    }
+
+    fn messages(&self, len: usize) -> Vec<(NlMsgHdr, usize)> {
+        let mut messages = Vec::new();
+        let mut offset = 0;
+        while offset + mem::size_of::<NlMsgHdr>() <= len {
+            let header = self.read::<NlMsgHdr>(offset);
+            let msg_len = header.nlmsg_len as usize;
+            if msg_len < mem::size_of::<NlMsgHdr>() || offset + msg_len > len {
+                break;
+            }
+            messages.push((header, offset));
+            offset += align(msg_len);
+        }
+        return messages;
+    }
+}
+
+impl Drop for LinkSocket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd); }
+    }
+}
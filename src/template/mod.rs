@@ -0,0 +1,202 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// A small subset of mustache, enough for config file templates:
+//
+//   {{name}}              the value of name, or nothing if it isn't set
+//   {{#name}}...{{/name}} once for each item if name is a list, with the
+//                         item's values in scope, otherwise once if name is
+//                         set, non-empty and not false
+//   {{^name}}...{{/name}} once if name is unset, empty or false
+//   {{! comment }}        nothing
+//
+// Names not found in a list item are looked up in the enclosing scopes. A
+// line holding nothing but a section tag or comment is dropped entirely, so
+// they can sit on lines of their own without leaving blank lines behind.
+// There is no escaping, since nothing here ends up in HTML.
+
+use std::collections::BTreeMap;
+use std::result;
+
+pub type Context = BTreeMap<String, Value>;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Text(String),
+    Flag(bool),
+    List(Vec<Context>),
+    Map(Context),
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Variable(String),
+    Section(String, bool, Vec<Node>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> result::Result<Template, String> {
+        let source = strip_standalone(source);
+        // Open sections, innermost last, with the nodes parsed inside each
+        let mut open: Vec<(String, bool, Vec<Node>)> = Vec::new();
+        let mut nodes = Vec::new();
+        let mut rest = &source[..];
+        while !rest.is_empty() {
+            let start = match rest.find("{{") {
+                Some(start) => start,
+                None => {
+                    nodes.push(Node::Text(String::from(rest)));
+                    break;
+                },
+            };
+            if start > 0 {
+                nodes.push(Node::Text(String::from(&rest[..start])));
+            }
+            let end = match rest[start..].find("}}") {
+                Some(end) => start + end,
+                None => return Err(format!("unterminated tag: {}", &rest[start..])),
+            };
+            let tag = rest[start + 2..end].trim();
+            rest = &rest[end + 2..];
+            if tag.starts_with('!') {
+                continue;
+            }
+            if tag.starts_with('#') || tag.starts_with('^') {
+                let name = try!(tag_name(tag));
+                open.push((name, tag.starts_with('^'), nodes));
+                nodes = Vec::new();
+            } else if tag.starts_with('/') {
+                let name = try!(tag_name(tag));
+                let (opened, inverted, outer) = match open.pop() {
+                    Some(section) => section,
+                    None => return Err(format!("{{{{/{}}}}} closes nothing", name)),
+                };
+                if opened != name {
+                    return Err(format!("{{{{/{}}}}} closes {{{{#{}}}}}", name, opened));
+                }
+                let section = Node::Section(name, inverted, nodes);
+                nodes = outer;
+                nodes.push(section);
+            } else if tag.is_empty() {
+                return Err(String::from("empty tag"));
+            } else {
+                nodes.push(Node::Variable(String::from(tag)));
+            }
+        }
+        match open.pop() {
+            Some((name, _, _)) => Err(format!("{{{{#{}}}}} is never closed", name)),
+            None => Ok(Template { nodes: nodes }),
+        }
+    }
+
+    pub fn render(&self, context: &Context) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, &mut vec![context], &mut out);
+        return out;
+    }
+}
+
+fn tag_name(tag: &str) -> result::Result<String, String> {
+    let name = tag[1..].trim();
+    if name.is_empty() {
+        return Err(format!("{{{{{}}}}} has no name", tag));
+    }
+    Ok(String::from(name))
+}
+
+fn is_standalone(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.starts_with("{{") && trimmed.ends_with("}}")
+        && trimmed[2..].find("{{").is_none()
+        && (trimmed[2..].starts_with('#') || trimmed[2..].starts_with('^')
+            || trimmed[2..].starts_with('/') || trimmed[2..].starts_with('!'))
+}
+
+fn strip_standalone(source: &str) -> String {
+    let mut out = String::new();
+    let mut rest = source;
+    while !rest.is_empty() {
+        let (line, next) = match rest.find('\n') {
+            Some(index) => (&rest[..index + 1], &rest[index + 1..]),
+            None => (rest, ""),
+        };
+        if is_standalone(line) {
+            out.push_str(line.trim());
+        } else {
+            out.push_str(line);
+        }
+        rest = next;
+    }
+    return out;
+}
+
+fn lookup<'a>(name: &str, stack: &Vec<&'a Context>) -> Option<&'a Value> {
+    for context in stack.iter().rev() {
+        match context.get(name) {
+            Some(value) => return Some(value),
+            None => {},
+        }
+    }
+    return None;
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    match value {
+        None => false,
+        Some(&Value::Text(ref text)) => !text.is_empty(),
+        Some(&Value::Flag(flag)) => flag,
+        Some(&Value::List(ref items)) => !items.is_empty(),
+        Some(&Value::Map(_)) => true,
+    }
+}
+
+fn render_nodes<'a>(nodes: &[Node], stack: &mut Vec<&'a Context>, out: &mut String) {
+    for node in nodes.iter() {
+        match *node {
+            Node::Text(ref text) => out.push_str(text),
+            Node::Variable(ref name) => match lookup(name, stack) {
+                Some(&Value::Text(ref text)) => out.push_str(text),
+                Some(&Value::Flag(flag)) => out.push_str(if flag { "true" } else { "false" }),
+                _ => {},
+            },
+            Node::Section(ref name, true, ref children) => {
+                if !is_truthy(lookup(name, stack)) {
+                    render_nodes(children, stack, out);
+                }
+            },
+            Node::Section(ref name, false, ref children) => match lookup(name, stack) {
+                Some(&Value::List(ref items)) => for item in items.iter() {
+                    stack.push(item);
+                    render_nodes(children, stack, out);
+                    stack.pop();
+                },
+                Some(&Value::Map(ref map)) => {
+                    stack.push(map);
+                    render_nodes(children, stack, out);
+                    stack.pop();
+                },
+                value => if is_truthy(value) {
+                    render_nodes(children, stack, out);
+                },
+            },
+        }
+    }
+}
@@ -0,0 +1,318 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Fake roots for trying glean out without the hardware, built from a
+// description of the NICs and files that should be there:
+//
+//   let root = try!(FakeRoot::new()
+//       .nic(Nic::ethernet("eth0", "fa:16:3e:00:00:01").driver("virtio_net"))
+//       .nic(Nic::ethernet("eth1", "fa:16:3e:00:00:02").carrier(false))
+//       .bond("bond0", &["eth0", "eth1"], "802.3ad")
+//       .network_info(json)
+//       .debian()
+//       .build());
+//   let interfaces = try!(SysInterfaces::new(&root.root(), ...));
+//
+// Only what glean reads is there: sys/class/net/<name>/ with address,
+// addr_assign_type, type, carrier and ifindex, device/driver and
+// device/subsystem links, the marker directories for wireless, bridges and
+// bonds, and files under the root. The tree is removed when the TempRoot
+// is dropped.
+//
+// Built with the testing feature.
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ::{Error, Result};
+
+// Link types from include/uapi/linux/if_arp.h
+const ARPHRD_ETHER: u16 = 1;
+const ARPHRD_INFINIBAND: u16 = 32;
+const ARPHRD_LOOPBACK: u16 = 772;
+
+// addr_assign_type values from include/uapi/linux/netdevice.h
+const NET_ADDR_PERM: u8 = 0;
+const NET_ADDR_STOLEN: u8 = 2;
+const NET_ADDR_SET: u8 = 3;
+
+static NEXT_ROOT: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone)]
+pub struct Nic {
+    name: String,
+    address: String,
+    link_type: u16,
+    addr_assign_type: u8,
+    carrier: bool,
+    driver: Option<String>,
+    bus: Option<String>,
+    wireless: bool,
+    bridge: bool,
+    bond: Option<(Vec<String>, String)>,
+    master: Option<String>,
+}
+
+impl Nic {
+    // A NIC with a permanent address, carrier and no driver or bus
+    pub fn ethernet(name: &str, address: &str) -> Nic {
+        Nic {
+            name: String::from(name),
+            address: String::from(address),
+            link_type: ARPHRD_ETHER,
+            addr_assign_type: NET_ADDR_PERM,
+            carrier: true,
+            driver: None,
+            bus: None,
+            wireless: false,
+            bridge: false,
+            bond: None,
+            master: None,
+        }
+    }
+
+    // An IPoIB interface, with its 20 byte address
+    pub fn infiniband(name: &str, address: &str) -> Nic {
+        Nic { link_type: ARPHRD_INFINIBAND, ..Nic::ethernet(name, address) }
+    }
+
+    pub fn loopback() -> Nic {
+        Nic { link_type: ARPHRD_LOOPBACK, ..Nic::ethernet("lo", "00:00:00:00:00:00") }
+    }
+
+    pub fn carrier(mut self, carrier: bool) -> Nic {
+        self.carrier = carrier;
+        self
+    }
+
+    pub fn addr_assign_type(mut self, addr_assign_type: u8) -> Nic {
+        self.addr_assign_type = addr_assign_type;
+        self
+    }
+
+    pub fn driver(mut self, driver: &str) -> Nic {
+        self.driver = Some(String::from(driver));
+        self
+    }
+
+    pub fn bus(mut self, bus: &str) -> Nic {
+        self.bus = Some(String::from(bus));
+        self
+    }
+
+    pub fn wireless(mut self) -> Nic {
+        self.wireless = true;
+        self
+    }
+
+    pub fn bridge(mut self) -> Nic {
+        self.bridge = true;
+        self
+    }
+
+    fn write(&self, net: &PathBuf, ifindex: usize) -> Result<()> {
+        let dir = net.join(&self.name);
+        try!(write_file(&dir.join("address"), &format!("{}\n", self.address)));
+        try!(write_file(&dir.join("addr_assign_type"), &format!("{}\n", self.addr_assign_type)));
+        try!(write_file(&dir.join("type"), &format!("{}\n", self.link_type)));
+        try!(write_file(&dir.join("carrier"), if self.carrier { "1\n" } else { "0\n" }));
+        try!(write_file(&dir.join("ifindex"), &format!("{}\n", ifindex)));
+        if let Some(ref driver) = self.driver {
+            try!(link(&format!("../../../../bus/{}/drivers/{}", self.bus.as_ref().map(|bus| &bus[..]).unwrap_or("pci"), driver), &dir.join("device/driver")));
+        }
+        if let Some(ref bus) = self.bus {
+            try!(link(&format!("../../../../bus/{}", bus), &dir.join("device/subsystem")));
+        }
+        if self.wireless {
+            try!(make_dir(&dir.join("wireless")));
+        }
+        if self.bridge {
+            try!(make_dir(&dir.join("bridge")));
+        }
+        if let Some((ref slaves, ref mode)) = self.bond {
+            try!(write_file(&dir.join("bonding/slaves"), &format!("{}\n", slaves.join(" "))));
+            try!(write_file(&dir.join("bonding/mode"), &format!("{}\n", mode)));
+        }
+        if let Some(ref master) = self.master {
+            try!(link(&format!("../{}", master), &dir.join("master")));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FakeRoot {
+    nics: Vec<Nic>,
+    vlans: Vec<(String, String, u16)>,
+    files: Vec<(String, String)>,
+    dirs: Vec<String>,
+}
+
+impl FakeRoot {
+    pub fn new() -> FakeRoot {
+        FakeRoot {
+            nics: Vec::new(),
+            vlans: Vec::new(),
+            files: Vec::new(),
+            dirs: Vec::new(),
+        }
+    }
+
+    pub fn nic(mut self, nic: Nic) -> FakeRoot {
+        self.nics.push(nic);
+        self
+    }
+
+    // A bond over NICs added before it. Like the kernel's, it takes the
+    // address of its first slave.
+    pub fn bond(mut self, name: &str, slaves: &[&str], mode: &str) -> FakeRoot {
+        let address = self.nics.iter()
+            .find(|nic| slaves.first() == Some(&&nic.name[..]))
+            .map(|nic| nic.address.clone())
+            .unwrap_or(String::from("00:00:00:00:00:00"));
+        for nic in self.nics.iter_mut().filter(|nic| slaves.contains(&&nic.name[..])) {
+            nic.master = Some(String::from(name));
+        }
+        let mut bond = Nic::ethernet(name, &address).addr_assign_type(NET_ADDR_SET);
+        bond.bond = Some((slaves.iter().map(|slave| slave.to_string()).collect(), String::from(mode)));
+        self.nics.push(bond);
+        self
+    }
+
+    // A vlan on a NIC or bond added before it, which shares its address
+    pub fn vlan(mut self, name: &str, parent: &str, vlan_id: u16) -> FakeRoot {
+        let address = self.nics.iter()
+            .find(|nic| nic.name == parent)
+            .map(|nic| nic.address.clone())
+            .unwrap_or(String::from("00:00:00:00:00:00"));
+        self.nics.push(Nic::ethernet(name, &address).addr_assign_type(NET_ADDR_STOLEN));
+        self.vlans.push((String::from(name), String::from(parent), vlan_id));
+        self
+    }
+
+    // Any file, at a path relative to the root
+    pub fn file(mut self, path: &str, content: &str) -> FakeRoot {
+        self.files.push((String::from(path), String::from(content)));
+        self
+    }
+
+    pub fn dir(mut self, path: &str) -> FakeRoot {
+        self.dirs.push(String::from(path));
+        self
+    }
+
+    pub fn network_info(self, json: &str) -> FakeRoot {
+        self.file("mnt/config/openstack/latest/network_info.json", json)
+    }
+
+    pub fn vendor_data(self, json: &str) -> FakeRoot {
+        self.file("mnt/config/openstack/latest/vendor_data.json", json)
+    }
+
+    pub fn meta_data(self, json: &str) -> FakeRoot {
+        self.file("mnt/config/openstack/latest/meta_data.json", json)
+    }
+
+    pub fn os_release(self, id: &str) -> FakeRoot {
+        self.file("etc/os-release", &format!("ID={}\n", id))
+    }
+
+    // Enough of Debian for platform detection and ifupdown's sources
+    pub fn debian(self) -> FakeRoot {
+        self.os_release("debian")
+            .file("etc/network/interfaces", "auto lo\niface lo inet loopback\n\nsource /etc/network/interfaces.d/*\n")
+            .dir("etc/network/interfaces.d")
+    }
+
+    pub fn redhat(self) -> FakeRoot {
+        self.os_release("rhel")
+            .file("etc/redhat-release", "Red Hat Enterprise Linux release 7.2 (Maipo)\n")
+            .dir("etc/sysconfig/network-scripts")
+    }
+
+    // Writes it all out under a new directory in the system temp dir
+    pub fn build(&self) -> Result<TempRoot> {
+        let path = ::std::env::temp_dir().join(format!("glean-root-{}-{}",
+            ::std::process::id(), NEXT_ROOT.fetch_add(1, Ordering::SeqCst)));
+        // Removed if anything below fails
+        let root = TempRoot { path: path };
+        let net = root.path.join("sys/class/net");
+        try!(make_dir(&net));
+        for (index, nic) in self.nics.iter().enumerate() {
+            try!(nic.write(&net, index + 1));
+        }
+        if !self.vlans.is_empty() {
+            let mut config = String::from("VLAN Dev name    | VLAN ID\nName-Type: VLAN_NAME_TYPE_RAW_PLUS_VID_NO_PAD\n");
+            for &(ref name, ref parent, vlan_id) in self.vlans.iter() {
+                config.push_str(&format!("{:<15}| {}  | {}\n", name, vlan_id, parent));
+            }
+            try!(write_file(&root.path.join("proc/net/vlan/config"), &config));
+        }
+        for dir in self.dirs.iter() {
+            try!(make_dir(&root.path.join(dir)));
+        }
+        for &(ref path, ref content) in self.files.iter() {
+            try!(write_file(&root.path.join(path), content));
+        }
+        Ok(root)
+    }
+}
+
+#[derive(Debug)]
+pub struct TempRoot {
+    path: PathBuf,
+}
+
+impl TempRoot {
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    // The root as glean's --root takes it
+    pub fn root(&self) -> Option<String> {
+        Some(self.path.to_string_lossy().into_owned())
+    }
+}
+
+impl Drop for TempRoot {
+    fn drop(&mut self) {
+        match fs::remove_dir_all(&self.path) {
+            Ok(_) => {},
+            Err(why) => debug!("couldn't remove {}: {}", self.path.display(), why),
+        }
+    }
+}
+
+fn make_dir(path: &PathBuf) -> Result<()> {
+    fs::create_dir_all(path).map_err(|why| Error::Io(path.clone(), why))
+}
+
+fn write_file(path: &PathBuf, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        try!(make_dir(&parent.to_path_buf()));
+    }
+    let mut file = try!(File::create(path).map_err(|why| Error::Io(path.clone(), why)));
+    file.write_all(content.as_bytes()).map_err(|why| Error::Io(path.clone(), why))
+}
+
+fn link(target: &str, path: &PathBuf) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        try!(make_dir(&parent.to_path_buf()));
+    }
+    symlink(target, path).map_err(|why| Error::Io(path.clone(), why))
+}
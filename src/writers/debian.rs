@@ -0,0 +1,92 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use ::Result;
+use ::live_networks::FileToWrite;
+use ::model::Device;
+use ::template::Template;
+use super::interfaces::{sourced_name, Interfaces};
+use super::{device_context, list_dir, load_template, validate, Writer};
+
+// ifupdown stanzas, a file per interface in /etc/network/interfaces.d
+pub struct DebianWriter {
+    root: PathBuf,
+    template: Template,
+    // As the root had it when the writer was made
+    interfaces: Interfaces,
+}
+
+impl DebianWriter {
+    pub fn new(root: &PathBuf) -> DebianWriter {
+        DebianWriter {
+            root: root.clone(),
+            template: load_template(root, "debian", "interface", include_str!("templates/debian/interface")),
+            interfaces: Interfaces::load(root),
+        }
+    }
+}
+
+impl Writer for DebianWriter {
+    // Named so that source-directory reads it, so a vlan like bond0.101
+    // goes in bond0-101
+    fn interface_file(&self, iface: &String) -> PathBuf {
+        return self.root.clone().join("etc/network/interfaces.d").join(sourced_name(iface));
+    }
+
+    // File names don't always match the interface, so go by the stanzas in
+    // each file
+    fn configured_interfaces(&self) -> Vec<String> {
+        let dir = self.root.join("etc/network/interfaces.d");
+        let mut ifaces = Vec::new();
+        for name in list_dir(&dir) {
+            let path = dir.join(&name);
+            for stanza in Interfaces::load_file(&self.root, &path).stanzas {
+                let ours = self.interface_file(&stanza.iface) == path || self.extra_files(&stanza.iface).contains(&path);
+                if stanza.iface != "lo" && ours && !ifaces.contains(&stanza.iface) {
+                    ifaces.push(stanza.iface);
+                }
+            }
+        }
+        return ifaces;
+    }
+
+    // What ifquery would say, but about the root rather than this host
+    fn config_exists(&self, iface: &String) -> bool {
+        return self.config_file(iface).is_some();
+    }
+
+    fn config_file(&self, iface: &String) -> Option<PathBuf> {
+        return self.interfaces.configured_in(iface).cloned();
+    }
+
+    fn generate_config(&self, device: &Device) -> Result<FileToWrite> {
+        try!(validate(device));
+        let content = self.template.render(&device_context(device));
+        return Ok(FileToWrite::new(self.interface_file(&device.name), content));
+    }
+
+    // Where glean put a vlan's file before naming it for source-directory,
+    // so that one of ours left there doesn't duplicate the new one
+    fn extra_files(&self, iface: &String) -> Vec<PathBuf> {
+        let old = self.root.join("etc/network/interfaces.d").join(iface);
+        match old == self.interface_file(iface) {
+            true => Vec::new(),
+            false => vec![old],
+        }
+    }
+}
+
@@ -0,0 +1,198 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Enough of ifupdown's /etc/network/interfaces to tell which interfaces have
+// an iface stanza, and where, without asking ifquery on whatever host glean
+// happens to be running on. source and source-directory are followed the way
+// ifupdown does, with absolute paths taken to be under the root.
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use glob::glob;
+
+// How deep sources can nest before we stop following them, which is also
+// what stops an include loop
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct Stanza {
+    pub iface: String,
+    pub family: Option<String>,
+    pub method: Option<String>,
+    pub file: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct Interfaces {
+    pub stanzas: Vec<Stanza>,
+}
+
+impl Interfaces {
+    // Everything reachable from <root>/etc/network/interfaces. A missing or
+    // unreadable file just has no stanzas.
+    pub fn load(root: &PathBuf) -> Interfaces {
+        Interfaces::load_file(root, &root.join("etc/network/interfaces"))
+    }
+
+    // Everything reachable from one file, whether or not anything sources it
+    pub fn load_file(root: &PathBuf, path: &PathBuf) -> Interfaces {
+        let mut interfaces = Interfaces { stanzas: Vec::new() };
+        interfaces.read(root, path, 0);
+        interfaces
+    }
+
+    // The file with the first iface stanza for an interface, as ifquery
+    // would find it
+    pub fn configured_in(&self, iface: &str) -> Option<&PathBuf> {
+        self.stanzas.iter().find(|stanza| stanza.iface == iface).map(|stanza| &stanza.file)
+    }
+
+    fn read(&mut self, root: &PathBuf, path: &PathBuf, depth: usize) {
+        if depth > MAX_DEPTH {
+            warn!("Not following {}, sources nest too deeply", path.display());
+            return;
+        }
+        let mut content = String::new();
+        match File::open(path).and_then(|mut file| file.read_to_string(&mut content)) {
+            Ok(_) => {},
+            Err(why) => {
+                debug!("couldn't read {}: {}", path.display(), why);
+                return;
+            },
+        }
+        let dir = match path.parent() {
+            Some(dir) => dir.to_path_buf(),
+            None => root.clone(),
+        };
+        for line in logical_lines(&content) {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match (words.get(0).map(|word| *word), words.len()) {
+                // ifupdown2 allows leaving out the family and method
+                (Some("iface"), len) if len >= 2 && len <= 4 => self.stanzas.push(Stanza {
+                    iface: words[1].to_string(),
+                    family: words.get(2).map(|word| word.to_string()),
+                    method: words.get(3).map(|word| word.to_string()),
+                    file: path.clone(),
+                }),
+                (Some("iface"), _) => warn!("Ignoring malformed stanza in {}: {}", path.display(), line),
+                (Some("source"), 2) => {
+                    for file in source_files(root, &dir, words[1]) {
+                        self.read(root, &file, depth + 1);
+                    }
+                },
+                (Some("source-directory"), 2) => {
+                    for file in directory_files(&resolve(root, &dir, words[1])) {
+                        self.read(root, &file, depth + 1);
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+// Lines with comments dropped and backslash continuations joined
+fn logical_lines(content: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for line in content.lines() {
+        if current.is_empty() && line.trim().starts_with('#') {
+            continue;
+        }
+        if line.ends_with('\\') {
+            current.push_str(&line[..line.len() - 1]);
+            current.push(' ');
+            continue;
+        }
+        current.push_str(line);
+        lines.push(current.trim().to_string());
+        current = String::new();
+    }
+    if !current.trim().is_empty() {
+        lines.push(current.trim().to_string());
+    }
+    lines
+}
+
+// Absolute paths are under the root, relative ones next to the file that
+// names them
+fn resolve(root: &PathBuf, dir: &PathBuf, path: &str) -> PathBuf {
+    if path.starts_with('/') {
+        root.join(&path[1..])
+    } else {
+        dir.join(path)
+    }
+}
+
+// source takes a glob, matched in sorted order
+fn source_files(root: &PathBuf, dir: &PathBuf, pattern: &str) -> Vec<PathBuf> {
+    let pattern = resolve(root, dir, pattern);
+    let entries = match pattern.to_str().map(glob) {
+        Some(Ok(entries)) => entries,
+        _ => {
+            warn!("Ignoring unusable source {}", pattern.display());
+            return Vec::new();
+        },
+    };
+    let mut files: Vec<PathBuf> = entries.filter_map(|entry| entry.ok()).filter(|path| is_file(path)).collect();
+    files.sort();
+    files
+}
+
+// source-directory reads files named like run-parts would run them, so
+// editor backups and dpkg leftovers are skipped, but so is anything with a
+// '.' in it
+fn is_sourced_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+fn is_sourced_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_sourced_char)
+}
+
+// A file name source-directory will read, with '-' for anything it wouldn't
+pub fn sourced_name(name: &str) -> String {
+    name.chars().map(|c| if is_sourced_char(c) { c } else { '-' }).collect()
+}
+
+fn directory_files(dir: &PathBuf) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(why) => {
+            debug!("couldn't list {}: {}", dir.display(), why);
+            return Vec::new();
+        },
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        if let Ok(entry) = entry {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if is_sourced_name(&name) && is_file(&entry.path()) {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn is_file(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_file(),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,382 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Rendering devices into a platform's config files. Writers are looked up by
+// platform name in a Registry, which starts out with glean's own and can have
+// more added by crates using glean as a library.
+//
+// glean's own writers render templates, built in or dropped into
+// /etc/glean/templates/<platform>/ under the root, with the variables
+// device_context describes.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+use ::{Error, Result};
+use ::ip::Prefix;
+use ::live_networks::FileToWrite;
+use ::model::{Device, Kind, Method, Route};
+use ::template::{Context, Template, Value};
+
+pub use self::debian::DebianWriter;
+pub use self::redhat::RedHatWriter;
+
+mod debian;
+pub mod interfaces;
+mod redhat;
+
+/// A backend that knows where a platform keeps per-interface network config
+/// and how to write it. All paths are under the root it was made for.
+pub trait Writer {
+    /// Where the config for an interface lives
+    fn interface_file(&self, iface: &String) -> PathBuf;
+    /// Interfaces with a file where interface_file would put it
+    fn configured_interfaces(&self) -> Vec<String>;
+    /// Whether the platform already has config for an interface, wherever
+    /// it came from
+    fn config_exists(&self, iface: &String) -> bool;
+    /// The file holding the platform's existing config for an interface, if
+    /// it has any
+    fn config_file(&self, iface: &String) -> Option<PathBuf> {
+        if self.config_exists(iface) {
+            Some(self.interface_file(iface))
+        } else {
+            None
+        }
+    }
+    /// The file for a device. Use FileToWrite::new, which adds the marker
+    /// glean uses to recognise its own files. Devices the platform can't
+    /// represent are an Error::InvalidConfig.
+    fn generate_config(&self, device: &Device) -> Result<FileToWrite>;
+    /// Files besides interface_file that an interface's config can be
+    /// split across, such as one for its routes. Those of glean's that a
+    /// device turns out not to need are removed.
+    fn extra_files(&self, _iface: &String) -> Vec<PathBuf> {
+        Vec::new()
+    }
+    /// The extra files a device needs, made like generate_config's
+    fn generate_extra_config(&self, _device: &Device) -> Result<Vec<FileToWrite>> {
+        Ok(Vec::new())
+    }
+}
+
+struct Platform {
+    name: String,
+    aliases: Vec<String>,
+    detect: Box<Fn(&PathBuf) -> bool>,
+    factory: Box<Fn(&PathBuf) -> Box<Writer>>,
+}
+
+/// Writers by platform name, for --distro and for detecting the platform of
+/// a root when it isn't given
+pub struct Registry {
+    platforms: Vec<Platform>,
+}
+
+impl Registry {
+    /// A registry with no platforms at all
+    pub fn new() -> Registry {
+        Registry { platforms: Vec::new() }
+    }
+
+    /// The platforms glean supports out of the box
+    pub fn builtin() -> Registry {
+        let mut registry = Registry::new();
+        // Anything that isn't recognisably Debian is taken to be Red Hat
+        registry.register("redhat", |_| true, |root| Box::new(RedHatWriter::new(root)));
+        registry.register("debian", |root| is_dir(&root.join("etc/network")), |root| Box::new(DebianWriter::new(root)));
+        registry.alias("rhel", "redhat");
+        registry.alias("centos", "redhat");
+        registry.alias("fedora", "redhat");
+        registry.alias("ubuntu", "debian");
+        return registry;
+    }
+
+    /// Adds a platform, replacing any already registered under the name.
+    /// When detecting, platforms registered later are asked first.
+    pub fn register<D, F>(&mut self, name: &str, detect: D, factory: F)
+        where D: Fn(&PathBuf) -> bool + 'static, F: Fn(&PathBuf) -> Box<Writer> + 'static {
+        let name = name.to_lowercase();
+        let aliases = match self.platforms.iter().position(|platform| platform.name == name) {
+            Some(index) => self.platforms.remove(index).aliases,
+            None => Vec::new(),
+        };
+        self.platforms.push(Platform {
+            name: name,
+            aliases: aliases,
+            detect: Box::new(detect),
+            factory: Box::new(factory),
+        });
+    }
+
+    /// Another name a registered platform can be asked for by
+    pub fn alias(&mut self, alias: &str, name: &str) {
+        let name = name.to_lowercase();
+        match self.platforms.iter_mut().find(|platform| platform.name == name) {
+            Some(platform) => platform.aliases.push(alias.to_lowercase()),
+            None => warn!("Not adding alias {} for unknown platform {}", alias, name),
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.platforms.iter().map(|platform| platform.name.clone()).collect()
+    }
+
+    /// The registered name for a platform name or alias, in any case
+    pub fn resolve(&self, name: &str) -> Result<String> {
+        let wanted = name.to_lowercase();
+        match self.platforms.iter().find(|platform| platform.name == wanted || platform.aliases.contains(&wanted)) {
+            Some(platform) => Ok(platform.name.clone()),
+            None => Err(Error::UnsupportedPlatform(String::from(name))),
+        }
+    }
+
+    /// The platform a root looks like
+    pub fn detect(&self, root: &PathBuf) -> Result<String> {
+        match self.platforms.iter().rev().find(|platform| (platform.detect)(root)) {
+            Some(platform) => Ok(platform.name.clone()),
+            None => Err(Error::UnsupportedPlatform(format!("no known platform at {}", root.display()))),
+        }
+    }
+
+    /// A writer for a platform, by name or alias, working under root
+    pub fn writer(&self, name: &str, root: &PathBuf) -> Result<Box<Writer>> {
+        let name = try!(self.resolve(name));
+        let platform = self.platforms.iter().find(|platform| platform.name == name).unwrap();
+        Ok((platform.factory)(root))
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Registry {:?}", self.names())
+    }
+}
+
+fn is_dir(path: &PathBuf) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_dir(),
+        Err(_) => false,
+    }
+}
+
+fn list_dir(dir: &PathBuf) -> Vec<String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(why) => {
+            debug!("couldn't list {}: {}", dir.display(), why);
+            return Vec::new();
+        },
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(entry) => names.push(entry.file_name().to_string_lossy().into_owned()),
+            Err(_) => {},
+        }
+    }
+    names.sort();
+    return names;
+}
+
+// A template from /etc/glean/templates/<platform>/<name> if there is a usable
+// one, otherwise the built in one. A broken override shouldn't leave an
+// instance without networking.
+fn load_template(root: &PathBuf, platform: &str, name: &str, builtin: &str) -> Template {
+    let path = root.join("etc/glean/templates").join(platform).join(name);
+    let mut source = String::new();
+    match File::open(&path).and_then(|mut file| file.read_to_string(&mut source)) {
+        Ok(_) => match Template::parse(&source) {
+            Ok(template) => {
+                info!("Using template {}", path.display());
+                return template;
+            },
+            Err(why) => warn!("Ignoring template {}: {}", path.display(), why),
+        },
+        Err(why) => debug!("No template {}: {}", path.display(), why),
+    }
+    Template::parse(builtin).unwrap()
+}
+
+// The variables templates get for a device:
+//
+//   name               interface name
+//   method             dhcp, static or manual, with a flag of each name set
+//   ethernet           set for plain interfaces, not bonds or vlans
+//   hotplug            set for plain interfaces nothing else is built on
+//   hwaddr             hardware address, if known
+//   mtu                MTU, if the source gave one
+//   addresses          every address, see below
+//   ipv4_addresses     just the IPv4 ones
+//   ipv6_addresses     just the IPv6 ones
+//   ipv6_secondaries   every IPv6 address but the first, as space separated CIDRs
+//   routes             every route, see below
+//   dns                list of nameservers, each with address and number,
+//                      counting from 1
+//   dns_servers        the nameservers, space separated
+//   bond               for a bond, with slaves (space separated) and mode
+//   vlan               for a vlan, with parent and vlan_id
+//   master             the bond an interface is a slave of
+//
+// Each address has address, prefix, netmask, cidr, broadcast (IPv4 only),
+// family (inet or inet6), ipv4 or ipv6 set, index and first within its list,
+// first_of_family, and, on the first of its family, that family's routes.
+// Each route has network, prefix, netmask, cidr, gateway, family, ipv4 or
+// ipv6, and default for the first 0.0.0.0/0 or ::/0 of its family.
+pub fn device_context(device: &Device) -> Context {
+    let mut context = Context::new();
+    context.insert(String::from("name"), text(&device.name));
+    context.insert(String::from("method"), text(device.method.name()));
+    context.insert(String::from("dhcp"), Value::Flag(device.method == Method::Dhcp));
+    context.insert(String::from("static"), Value::Flag(device.method == Method::Static));
+    context.insert(String::from("manual"), Value::Flag(device.method == Method::Manual));
+    context.insert(String::from("ethernet"), Value::Flag(device.kind == Kind::Ethernet));
+    // Plain interfaces come up as udev finds them. Bonds, vlans and what they
+    // are built on are brought up at boot, in order.
+    context.insert(String::from("hotplug"), Value::Flag(device.kind == Kind::Ethernet && device.method != Method::Manual));
+    context.insert(String::from("hwaddr"), text(&optional(&device.hardware_address)));
+    context.insert(String::from("mtu"), text(&optional(&device.mtu)));
+    context.insert(String::from("master"), text(&optional(&device.master)));
+
+    let ipv4: Vec<&Prefix> = device.addresses.iter().filter(|address| !address.is_ipv6()).collect();
+    let ipv6: Vec<&Prefix> = device.addresses.iter().filter(|address| address.is_ipv6()).collect();
+    let routes = routes_context(&device.routes);
+    let mut families_seen = Vec::new();
+    let addresses = device.addresses.iter().enumerate().map(|(index, address)| {
+        let first_of_family = !families_seen.contains(&address.is_ipv6());
+        families_seen.push(address.is_ipv6());
+        let mut item = address_context(address, index);
+        let routes = device.routes.iter().zip(routes.iter())
+            .filter(|&(route, _)| first_of_family && route.destination.is_ipv6() == address.is_ipv6())
+            .map(|(_, context)| context.clone())
+            .collect();
+        item.insert(String::from("first_of_family"), Value::Flag(first_of_family));
+        item.insert(String::from("routes"), Value::List(routes));
+        item
+    }).collect();
+    context.insert(String::from("addresses"), Value::List(addresses));
+    context.insert(String::from("ipv4_addresses"), Value::List(
+        ipv4.iter().enumerate().map(|(index, address)| address_context(address, index)).collect()));
+    context.insert(String::from("ipv6_addresses"), Value::List(
+        ipv6.iter().enumerate().map(|(index, address)| address_context(address, index)).collect()));
+    let secondaries: Vec<String> = ipv6.iter().skip(1).map(|address| address.to_string()).collect();
+    context.insert(String::from("ipv6_secondaries"), text(&secondaries.join(" ")));
+    context.insert(String::from("routes"), Value::List(routes));
+
+    let dns: Vec<String> = device.dns.iter().map(|address| address.to_string()).collect();
+    context.insert(String::from("dns"), Value::List(dns.iter().enumerate().map(|(index, address)| {
+        let mut item = Context::new();
+        item.insert(String::from("address"), text(address));
+        item.insert(String::from("number"), text(&(index + 1).to_string()));
+        item
+    }).collect()));
+    context.insert(String::from("dns_servers"), text(&dns.join(" ")));
+
+    match device.kind {
+        Kind::Bond { ref slaves, ref mode } => {
+            let mut bond = Context::new();
+            bond.insert(String::from("slaves"), text(&slaves.join(" ")));
+            bond.insert(String::from("mode"), text(&optional(mode)));
+            context.insert(String::from("bond"), Value::Map(bond));
+        },
+        Kind::Vlan { ref parent, vlan_id } => {
+            let mut vlan = Context::new();
+            vlan.insert(String::from("parent"), text(parent));
+            vlan.insert(String::from("vlan_id"), text(&vlan_id.to_string()));
+            context.insert(String::from("vlan"), Value::Map(vlan));
+        },
+        Kind::Ethernet => {},
+    }
+    return context;
+}
+
+fn address_context(address: &Prefix, index: usize) -> Context {
+    let mut item = prefix_context(address);
+    item.insert(String::from("address"), text(&address.address().to_string()));
+    item.insert(String::from("broadcast"), text(&optional(&address.broadcast())));
+    item.insert(String::from("index"), text(&index.to_string()));
+    item.insert(String::from("first"), Value::Flag(index == 0));
+    item
+}
+
+// Only the first default route of each family is marked default, since a
+// platform can usually only take one gateway per family
+fn routes_context(routes: &Vec<Route>) -> Vec<Context> {
+    let mut defaults_seen = Vec::new();
+    routes.iter().map(|route| {
+        let family = route.destination.is_ipv6();
+        let default = route.destination.is_default() && !defaults_seen.contains(&family);
+        if default {
+            defaults_seen.push(family);
+        }
+        let mut item = prefix_context(&route.destination);
+        item.insert(String::from("network"), text(&route.destination.network().to_string()));
+        item.insert(String::from("gateway"), text(&route.gateway.to_string()));
+        item.insert(String::from("default"), Value::Flag(default));
+        item
+    }).collect()
+}
+
+// Names end up in file names and unquoted in config, so have to be ones the
+// kernel would take: at most 15 bytes, with no whitespace, '/' or ':'
+pub fn validate(device: &Device) -> Result<()> {
+    let mut names = vec![&device.name];
+    match device.kind {
+        Kind::Bond { ref slaves, ref mode } => {
+            names.extend(slaves.iter());
+            if let Some(ref mode) = *mode {
+                if mode.is_empty() || mode.contains(char::is_whitespace) {
+                    return Err(Error::InvalidConfig(device.name.clone(), format!("bond mode '{}'", mode)));
+                }
+            }
+        },
+        Kind::Vlan { ref parent, .. } => names.push(parent),
+        Kind::Ethernet => {},
+    }
+    if let Some(ref master) = device.master {
+        names.push(master);
+    }
+    for name in names {
+        if name.is_empty() || name.len() > 15 || name == "." || name == ".."
+            || name.contains(|c: char| c.is_whitespace() || c == '/' || c == ':') {
+            return Err(Error::InvalidConfig(device.name.clone(), format!("'{}' is not a usable interface name", name)));
+        }
+    }
+    Ok(())
+}
+
+fn prefix_context(prefix: &Prefix) -> Context {
+    let mut item = Context::new();
+    item.insert(String::from("prefix"), text(&prefix.prefix_len().to_string()));
+    item.insert(String::from("netmask"), text(&prefix.netmask().to_string()));
+    item.insert(String::from("cidr"), text(&prefix.to_string()));
+    item.insert(String::from("family"), text(if prefix.is_ipv6() { "inet6" } else { "inet" }));
+    item.insert(String::from("ipv4"), Value::Flag(!prefix.is_ipv6()));
+    item.insert(String::from("ipv6"), Value::Flag(prefix.is_ipv6()));
+    item
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(String::from(value))
+}
+
+fn optional<T: fmt::Display>(value: &Option<T>) -> String {
+    match *value {
+        Some(ref value) => value.to_string(),
+        None => String::new(),
+    }
+}
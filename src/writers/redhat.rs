@@ -0,0 +1,92 @@
+// Copyright (c) 2015 Hewlett-Packard Development Company, L.P.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::path::PathBuf;
+
+use ::Result;
+use ::live_networks::FileToWrite;
+use ::model::Device;
+use ::template::Template;
+use super::{device_context, list_dir, load_template, validate, Writer};
+
+// ifcfg files for initscripts, in /etc/sysconfig/network-scripts, with
+// static routes other than the gateway in route- and route6- files
+pub struct RedHatWriter {
+    root: PathBuf,
+    template: Template,
+    route_template: Template,
+    route6_template: Template,
+}
+
+impl RedHatWriter {
+    pub fn new(root: &PathBuf) -> RedHatWriter {
+        RedHatWriter {
+            root: root.clone(),
+            template: load_template(root, "redhat", "ifcfg", include_str!("templates/redhat/ifcfg")),
+            route_template: load_template(root, "redhat", "route", include_str!("templates/redhat/route")),
+            route6_template: load_template(root, "redhat", "route6", include_str!("templates/redhat/route6")),
+        }
+    }
+
+    fn script(&self, kind: &str, iface: &String) -> PathBuf {
+        return self.root.join("etc/sysconfig/network-scripts").join(format!("{}-{}", kind, iface));
+    }
+}
+
+impl Writer for RedHatWriter {
+    fn interface_file(&self, iface: &String) -> PathBuf {
+      return self.script("ifcfg", iface);
+    }
+
+    fn configured_interfaces(&self) -> Vec<String> {
+      let dir = self.root.join("etc/sysconfig/network-scripts");
+      return list_dir(&dir).into_iter()
+          .filter(|name| name.starts_with("ifcfg-") && name != "ifcfg-lo")
+          .map(|name| name["ifcfg-".len()..].to_string())
+          .collect();
+    }
+
+    fn config_exists(&self, iface: &String) -> bool {
+      match fs::metadata(self.interface_file(&iface)) {
+          Ok(metadata) => return metadata.is_file(),
+          Err(_) => return false,
+      }
+    }
+
+    fn generate_config(&self, device: &Device) -> Result<FileToWrite> {
+        try!(validate(device));
+        let content = self.template.render(&device_context(device));
+        return Ok(FileToWrite::new(self.interface_file(&device.name), content));
+    }
+
+    fn extra_files(&self, iface: &String) -> Vec<PathBuf> {
+        vec![self.script("route", iface), self.script("route6", iface)]
+    }
+
+    // No file at all for a device without routes of that family
+    fn generate_extra_config(&self, device: &Device) -> Result<Vec<FileToWrite>> {
+        try!(validate(device));
+        let context = device_context(device);
+        let mut files = Vec::new();
+        for &(kind, template) in [("route", &self.route_template), ("route6", &self.route6_template)].iter() {
+            let content = template.render(&context);
+            if !content.trim().is_empty() {
+                files.push(FileToWrite::new(self.script(kind, &device.name), content));
+            }
+        }
+        return Ok(files);
+    }
+}
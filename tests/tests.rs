@@ -15,7 +15,30 @@
 
 extern crate glean;
 use glean::config_drive::ConfigDrive;
+use glean::GleanConfig;
+use glean::cmdline::KernelCmdline;
+use glean::explain::{Report, Status};
+use glean::instance::Task;
+use glean::ip::Prefix;
+use glean::live_networks::{FileToWrite, LiveNetworks, Source};
+use glean::model::{Device, Method};
+use glean::overrides::Overrides;
+use glean::plan::Plan;
+use glean::settings::Settings;
+use glean::sys::{CarrierProbe, Filter, HardwareAddress, Rule, Strategy, SysInterfaces};
+use glean::template::{Context, Template, Value};
+use glean::writers::{Registry, Writer};
+use std::net::IpAddr;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::PathBuf;
 use std::string::ToString;
+use std::time::Duration;
+
+// What's in sysfs under a root, without bringing any links up
+fn sys_interfaces(root: &Option<String>) -> SysInterfaces {
+    SysInterfaces::new(root, &None, true, &CarrierProbe::new(Duration::from_secs(0), Strategy::None), &Filter::new()).unwrap()
+}
 
 #[test]
 fn it_works() {
@@ -23,10 +46,1062 @@ fn it_works() {
     let root = "samples/liberty";
     let config_drive = ConfigDrive::new(&Some(ToString::to_string(root)));
     match config_drive {
-        Some(interface) => {
-            println!("Network {:?}", interface.interface_map);
-            println!("DNS {:?}", interface.dns);
+        Ok(Some(interface)) => {
+            println!("Network {:?}", interface.network.devices);
+            println!("DNS {:?}", interface.network.dns);
         },
-        None => assert!(false),
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn empty_vendor_data_is_not_an_error() {
+
+    let root = "samples/hp";
+    match ConfigDrive::new(&Some(ToString::to_string(root))) {
+        Ok(None) => {},
+        _ => assert!(false),
+    }
+}
+
+#[test]
+fn explain_matches_config_drive() {
+
+    let root = Some(ToString::to_string("samples/liberty"));
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let sys_interfaces = sys_interfaces(&root);
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive.clone(), None, &root, &None).unwrap();
+    let report = Report::new(&sys_interfaces, &config_drive, &live_networks);
+    assert_eq!(report.interfaces.len(), 2);
+    for iface in report.interfaces.iter() {
+        match iface.status {
+            Status::Accepted(Source::ConfigDrive(_)) => {},
+            _ => assert!(false),
+        }
+        assert!(iface.network.is_some());
+    }
+}
+
+#[test]
+fn plan_reports_static_interfaces() {
+
+    let root = Some(ToString::to_string("samples/liberty"));
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let sys_interfaces = sys_interfaces(&root);
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &root, &Some(ToString::to_string("redhat"))).unwrap();
+    let output = live_networks.get_output().unwrap();
+    let plan = Plan::new(&sys_interfaces, &live_networks, &output);
+    assert_eq!(plan.platform, "redhat");
+    // eth1's routes other than the gateway get a file of their own
+    assert_eq!(plan.files.len(), 3);
+    for iface in plan.interfaces.iter() {
+        assert_eq!(iface.mode, "static");
+    }
+    for file in plan.files.iter() {
+        assert_eq!(file.action, "create");
+    }
+    // None of them are on disk yet
+    let diff = output.diff();
+    assert!(diff.starts_with("--- /dev/null\n"));
+    assert_eq!(diff.matches("--- /dev/null\n").count(), 3);
+}
+
+#[test]
+fn redhat_config_has_gateway_dns_and_routes() {
+
+    let root = Some(ToString::to_string("samples/liberty"));
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let sys_interfaces = sys_interfaces(&root);
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &root, &Some(ToString::to_string("redhat"))).unwrap();
+    let output = live_networks.get_output().unwrap();
+    let names: Vec<String> = output.files().iter()
+        .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, vec!["ifcfg-eth0", "ifcfg-eth1", "route-eth1"]);
+    let eth0 = &output.files()[0].content;
+    assert!(eth0.contains("GATEWAY=23.253.229.1\n"));
+    assert!(eth0.contains("DNS1=72.3.128.241\nDNS2=72.3.128.240\n"));
+    assert!(!output.files()[1].content.contains("GATEWAY"));
+    let routes = &output.files()[2].content;
+    assert!(routes.contains("\n10.176.0.0/12 via 10.208.160.1 dev eth1\n10.208.0.0/12 via 10.208.160.1 dev eth1\n"));
+}
+
+#[test]
+fn filter_rejects_excluded_interfaces() {
+
+    let root = Some(ToString::to_string("samples/liberty"));
+    let mut filter = Filter::new();
+    filter.exclude.push(Rule::from_str("name:eth1").unwrap());
+    let sys_interfaces = SysInterfaces::new(&root, &None, true, &CarrierProbe::new(Duration::from_secs(0), Strategy::None), &filter).unwrap();
+    assert_eq!(sys_interfaces.interfaces.len(), 1);
+    assert_eq!(sys_interfaces.interfaces[0].iface, "eth0");
+    assert_eq!(sys_interfaces.rejected.len(), 1);
+    assert_eq!(sys_interfaces.rejected[0].iface, "eth1");
+}
+
+#[test]
+fn hardware_addresses_match_across_spellings() {
+
+    let mac = HardwareAddress::parse("FA:16:3E:6D:1A:5C").unwrap();
+    assert!(mac.matches(&HardwareAddress::parse("fa-16-3e-6d-1a-5c").unwrap()));
+    assert!(mac.matches(&HardwareAddress::parse("fa16.3e6d.1a5c").unwrap()));
+    assert_eq!(mac.to_string(), "fa:16:3e:6d:1a:5c");
+
+    // Same port GUID behind different queue pair numbers
+    let ib = HardwareAddress::parse("80:00:02:08:fe:80:00:00:00:00:00:00:7c:fe:90:03:00:29:26:55").unwrap();
+    assert!(ib.is_infiniband());
+    assert!(ib.matches(&HardwareAddress::parse("a0:00:03:00:fe:80:00:00:00:00:00:00:7c:fe:90:03:00:29:26:55").unwrap()));
+    assert!(ib.matches(&HardwareAddress::parse("7c:fe:90:03:00:29:26:55").unwrap()));
+    assert!(ib.matches(&HardwareAddress::parse("7c:fe:90:29:26:55").unwrap()));
+    assert!(!ib.matches(&mac));
+
+    assert!(HardwareAddress::parse("").is_none());
+    assert!(HardwareAddress::parse("fa:16:3e:6d:1a:5").is_none());
+    assert!(HardwareAddress::parse("zz:16:3e:6d:1a:5c").is_none());
+}
+
+#[test]
+fn netmasks_become_prefixes() {
+
+    let address: IpAddr = "10.208.169.118".parse().unwrap();
+    let prefix = Prefix::from_netmask(address, "255.255.224.0").unwrap();
+    assert_eq!(prefix.prefix_len(), 19);
+    assert_eq!(prefix.to_string(), "10.208.169.118/19");
+    assert_eq!(prefix.network().to_string(), "10.208.160.0");
+    assert_eq!(prefix.broadcast().unwrap().to_string(), "10.208.191.255");
+    assert!(Prefix::from_netmask(address, "255.255.225.0").is_none());
+    assert!(Prefix::from_netmask(address, "ffff:ffff::").is_none());
+    assert!(Prefix::from_netmask(address, "33").is_none());
+
+    let address: IpAddr = "2001:db8::5".parse().unwrap();
+    let prefix = Prefix::from_netmask(address, "ffff:ffff:ffff:ffff::").unwrap();
+    assert_eq!(prefix.prefix_len(), 64);
+    assert_eq!(prefix.netmask().to_string(), "ffff:ffff:ffff:ffff::");
+    assert!(prefix.broadcast().is_none());
+
+    let default = Prefix::from_netmask("::".parse().unwrap(), "::").unwrap();
+    assert!(default.is_default());
+    assert_eq!(default.to_string(), "::/0");
+}
+
+#[test]
+fn bonds_and_vlans_are_built_on_their_members() {
+
+    let root = Some(ToString::to_string("samples/bond"));
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let sys_interfaces = sys_interfaces(&root);
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &root, &Some(ToString::to_string("debian"))).unwrap();
+    let output = live_networks.get_output().unwrap();
+    let names: Vec<String> = output.files().iter()
+        .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, vec!["bond0", "bond0-101", "eth0", "eth1"]);
+    assert!(output.files()[0].content.contains("bond-slaves eth0 eth1"));
+    assert!(output.files()[1].content.contains("vlan-raw-device bond0"));
+    assert!(output.files()[2].content.contains("bond-master bond0"));
+}
+
+#[test]
+fn plan_reports_bond_slaves_as_manual() {
+
+    let root = Some(ToString::to_string("samples/bond"));
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let sys_interfaces = sys_interfaces(&root);
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &root, &Some(ToString::to_string("debian"))).unwrap();
+    let plan = Plan::new(&sys_interfaces, &live_networks, &live_networks.get_output().unwrap());
+    assert_eq!(plan.interfaces.len(), 2);
+    for iface in plan.interfaces.iter() {
+        assert_eq!(iface.mode, "manual", "{}", iface.name);
+        assert_eq!(iface.source, Some(ToString::to_string("config_drive")));
+    }
+    assert!(plan.to_json().contains("\"mode\": \"manual\""));
+}
+
+#[test]
+fn plan_reports_dhcp_devices_as_dhcp() {
+
+    let root = Some(ToString::to_string("samples/liberty"));
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let sys_interfaces = sys_interfaces(&root);
+    let eth1 = sys_interfaces.interfaces.iter().find(|iface| iface.iface == "eth1").unwrap();
+    let overrides = Overrides {
+        network: glean::model::Config { devices: vec![Device::dhcp(eth1)], dns: Vec::new() },
+        kernel_cmdline: glean::model::Config { devices: Vec::new(), dns: Vec::new() },
+    };
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, Some(overrides), &root, &Some(ToString::to_string("debian"))).unwrap();
+    let output = live_networks.get_output().unwrap();
+    let plan = Plan::new(&sys_interfaces, &live_networks, &output);
+    for iface in plan.interfaces.iter() {
+        match &iface.name[..] {
+            "eth0" => assert_eq!(iface.mode, "static"),
+            _ => assert_eq!(iface.mode, "dhcp"),
+        }
+    }
+    assert!(output.files().iter().any(|file| file.content.contains("iface eth1 inet dhcp")));
+}
+
+struct ListWriter {
+    root: PathBuf,
+}
+
+impl Writer for ListWriter {
+    fn interface_file(&self, iface: &String) -> PathBuf {
+        self.root.join("etc/list").join(iface)
+    }
+
+    fn configured_interfaces(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn config_exists(&self, _: &String) -> bool {
+        false
+    }
+
+    fn generate_config(&self, device: &Device) -> glean::Result<FileToWrite> {
+        Ok(FileToWrite::new(self.interface_file(&device.name), format!("{} {}", device.name, device.addresses.len())))
+    }
+}
+
+#[test]
+fn registered_writers_are_used_for_their_distro() {
+
+    let root = Some(ToString::to_string("samples/liberty"));
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let sys_interfaces = sys_interfaces(&root);
+    let mut registry = Registry::builtin();
+    registry.register("list", |_| false, |root| Box::new(ListWriter { root: root.clone() }));
+    registry.alias("lists", "list");
+    let live_networks = LiveNetworks::with_registry(&sys_interfaces, config_drive, None, &root, &Some(ToString::to_string("Lists")), &registry).unwrap();
+    assert_eq!(live_networks.platform_name(), "list");
+    let output = live_networks.get_output().unwrap();
+    assert_eq!(output.files().len(), 2);
+    assert!(output.files()[0].path.ends_with("etc/list/eth0"));
+    assert!(output.files()[0].content.ends_with("eth0 1\n"));
+
+    match LiveNetworks::new(&sys_interfaces, None, None, &root, &Some(ToString::to_string("list"))) {
+        Err(glean::Error::UnsupportedPlatform(_)) => {},
+        _ => assert!(false),
+    }
+
+    // And the same through glean::run
+    let mut registry = Registry::builtin();
+    registry.register("list", |_| false, |root| Box::new(ListWriter { root: root.clone() }));
+    let config = GleanConfig::builder()
+        .root("samples/liberty")
+        .distro("list")
+        .registry(registry)
+        .carrier(Duration::from_secs(0), Strategy::None)
+        .tasks(&[Task::Network])
+        .noop(true)
+        .build();
+    let network = glean::run(&config).unwrap().network.unwrap();
+    assert_eq!(network.plan.platform, "list");
+    assert!(network.files.files()[0].path.ends_with("etc/list/eth0"));
+}
+
+#[test]
+fn templates_render_sections_and_lists() {
+
+    let template = Template::parse("name={{name}}\n{{#items}}\n- {{value}} of {{name}}\n{{/items}}\n{{^items}}\nnone\n{{/items}}\n{{! dropped }}\nend\n").unwrap();
+    let mut context = Context::new();
+    context.insert(String::from("name"), Value::Text(String::from("eth0")));
+    let items = vec!["a", "b"].into_iter().map(|value| {
+        let mut item = Context::new();
+        item.insert(String::from("value"), Value::Text(String::from(value)));
+        item
+    }).collect();
+    context.insert(String::from("items"), Value::List(items));
+    assert_eq!(template.render(&context), "name=eth0\n- a of eth0\n- b of eth0\nend\n");
+
+    context.insert(String::from("items"), Value::List(Vec::new()));
+    assert_eq!(template.render(&context), "name=eth0\nnone\nend\n");
+
+    assert!(Template::parse("{{#a}}").is_err());
+    assert!(Template::parse("{{#a}}{{/b}}").is_err());
+    assert!(Template::parse("{{a").is_err());
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn templates_can_be_overridden_under_the_root() {
+    use glean::testing::FakeRoot;
+    use glean::writers::RedHatWriter;
+
+    let fake = FakeRoot::new()
+        .file("etc/glean/templates/redhat/ifcfg", "DEVICE={{name}}\nDEFROUTE=no\n{{#ipv4_addresses}}\nIPADDR={{address}}\nPREFIX={{prefix}}\n{{/ipv4_addresses}}\n")
+        .build().unwrap();
+    let root = fake.path();
+    let templates = root.join("etc/glean/templates/redhat");
+
+    let live_root = Some(ToString::to_string("samples/liberty"));
+    let config_drive = ConfigDrive::new(&live_root).unwrap().unwrap();
+    let device = config_drive.network.devices[0].clone();
+    let file = RedHatWriter::new(&root).generate_config(&device).unwrap();
+    assert!(file.content.ends_with("DEFROUTE=no\nIPADDR=23.253.229.154\nPREFIX=24\n"));
+
+    // A broken override falls back to the built in template
+    fs::write(templates.join("ifcfg"), "{{#ipv4_addresses}}").unwrap();
+    let file = RedHatWriter::new(&root).generate_config(&device).unwrap();
+    assert!(file.content.contains("NETMASK=255.255.255.0"));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn debian_config_is_found_through_sources() {
+    use glean::testing::FakeRoot;
+    use glean::writers::DebianWriter;
+    use glean::writers::interfaces::Interfaces;
+
+    let fake = FakeRoot::new()
+        .file("etc/network/interfaces", "auto lo\niface lo inet loopback\n# iface eth9 inet dhcp\nsource interfaces.d/*\nsource-directory /etc/net.d\n")
+        .file("etc/network/interfaces.d/eth0", "auto eth0\niface eth0 inet \\\n    dhcp\n")
+        .file("etc/net.d/extra", "iface eth1 inet static\n    address 10.0.0.2/24\n")
+        // source-directory skips names run-parts wouldn't run
+        .file("etc/net.d/extra.bak", "iface eth2 inet dhcp\n")
+        // and an include loop doesn't hang
+        .file("etc/network/interfaces.d/loop", "source /etc/network/interfaces\n")
+        .build().unwrap();
+    let root = fake.path();
+    let network = root.join("etc/network");
+
+    let interfaces = Interfaces::load(&root);
+    assert_eq!(interfaces.configured_in("eth0"), Some(&network.join("interfaces.d/eth0")));
+    assert_eq!(interfaces.configured_in("eth1"), Some(&root.join("etc/net.d/extra")));
+    assert_eq!(interfaces.configured_in("eth2"), None);
+    assert_eq!(interfaces.configured_in("eth9"), None);
+
+    let writer = DebianWriter::new(&root);
+    assert!(writer.config_exists(&ToString::to_string("lo")));
+    assert!(!writer.config_exists(&ToString::to_string("eth3")));
+    assert_eq!(writer.config_file(&ToString::to_string("eth1")), Some(root.join("etc/net.d/extra")));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn debian_vlans_are_written_where_source_directory_reads() {
+    use glean::live_networks::Action;
+    use glean::testing::FakeRoot;
+    use glean::writers::DebianWriter;
+    use glean::writers::interfaces::Interfaces;
+
+    let fake = FakeRoot::new()
+        .file("etc/network/interfaces", "auto lo\niface lo inet loopback\nsource-directory /etc/network/interfaces.d\n")
+        .dir("etc/network/interfaces.d")
+        .build().unwrap();
+    let root = fake.path();
+    let target = fake.root();
+    let sample = Some(ToString::to_string("samples/bond"));
+    let sys_interfaces = sys_interfaces(&sample);
+    let output = || {
+        let config_drive = ConfigDrive::new(&sample).unwrap();
+        LiveNetworks::new(&sys_interfaces, config_drive, None, &target, &Some(ToString::to_string("debian"))).unwrap().get_output().unwrap()
+    };
+
+    output().write().unwrap();
+    let vlan = root.join("etc/network/interfaces.d/bond0-101");
+    assert!(vlan.exists());
+    assert_eq!(Interfaces::load(root).configured_in("bond0.101"), Some(&vlan));
+    let mut configured = DebianWriter::new(root).configured_interfaces();
+    configured.sort();
+    assert_eq!(configured, vec!["bond0", "bond0.101", "eth0", "eth1"]);
+
+    // Next time round it's all there already, and none of it is stale
+    for file in output().files().iter() {
+        assert_eq!(file.action, Action::Skip, "{}", file.path.display());
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn debian_files_under_old_names_are_replaced() {
+    use glean::live_networks::Action;
+    use glean::testing::FakeRoot;
+    use glean::writers::interfaces::Interfaces;
+
+    // As glean wrote it before naming files for source-directory, and read
+    // through interfaces.d/*
+    let stanza = String::from("auto bond0.101\niface bond0.101 inet static\n    vlan-raw-device bond0\n");
+    let fake = FakeRoot::new()
+        .debian()
+        .file("etc/network/interfaces.d/bond0.101", &FileToWrite::new(PathBuf::new(), stanza).content)
+        .build().unwrap();
+    let target = fake.root();
+    let sample = Some(ToString::to_string("samples/bond"));
+    let sys_interfaces = sys_interfaces(&sample);
+    let output = || {
+        let config_drive = ConfigDrive::new(&sample).unwrap();
+        LiveNetworks::new(&sys_interfaces, config_drive, None, &target, &Some(ToString::to_string("debian"))).unwrap().get_output().unwrap()
+    };
+
+    let old = fake.path().join("etc/network/interfaces.d/bond0.101");
+    let new = fake.path().join("etc/network/interfaces.d/bond0-101");
+    let files = output();
+    assert_eq!(files.files().iter().find(|file| file.path == new).unwrap().action, Action::Create);
+    assert_eq!(files.files().iter().find(|file| file.path == old).unwrap().action, Action::Remove);
+    files.write().unwrap();
+    assert!(new.exists() && !old.exists());
+    assert_eq!(Interfaces::load(fake.path()).configured_in("bond0.101"), Some(&new));
+    for file in output().files().iter() {
+        assert_eq!(file.action, Action::Skip, "{}", file.path.display());
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn interfaces_sharing_a_file_are_rejected() {
+    use glean::testing::{FakeRoot, Nic};
+
+    // eth0.1 and eth0-1 would both be interfaces.d/eth0-1
+    let fake = FakeRoot::new()
+        .nic(Nic::ethernet("eth0", "fa:16:3e:00:00:01"))
+        .network_info(r#"{
+            "links": [
+                {"id": "interface0", "type": "phy", "ethernet_mac_address": "fa:16:3e:00:00:01"},
+                {"id": "vlan0", "type": "vlan", "vlan_link": "interface0", "vlan_id": 1},
+                {"id": "vlan1", "type": "vlan", "vlan_link": "interface0", "vlan_id": 2, "name": "eth0-1"}
+            ],
+            "networks": [],
+            "services": []
+        }"#)
+        .debian()
+        .build().unwrap();
+    let root = fake.root();
+    let sys_interfaces = sys_interfaces(&root);
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &root, &None).unwrap();
+    match live_networks.get_output() {
+        Err(glean::Error::InvalidConfig(iface, why)) => {
+            assert_eq!(iface, "eth0.1");
+            assert!(why.contains("eth0-1"), "{}", why);
+        },
+        other => panic!("got {:?}", other.map(|files| files.files().len())),
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn overrides_win_over_config_drive() {
+    use glean::testing::FakeRoot;
+
+    // eth0 is in both, and the second link is on no interface at all
+    let fake = FakeRoot::new().file("etc/glean/overrides.json", r#"{
+        "links": [
+            {"id": "local0", "ethernet_mac_address": "bc:76:4e:01:62:86"},
+            {"id": "local1", "ethernet_mac_address": "fa:16:3e:00:00:09"}
+        ],
+        "networks": [
+            {"id": "override0", "network_id": "override0", "link": "local0", "type": "ipv4", "ip_address": "192.0.2.10", "netmask": "255.255.255.0",
+             "routes": [{"network": "198.51.100.0", "netmask": "255.255.255.0", "gateway": "192.0.2.1"}]},
+            {"id": "override1", "network_id": "override1", "link": "local1", "type": "ipv4", "ip_address": "192.0.2.20", "netmask": "255.255.255.0",
+             "routes": []}
+        ],
+        "services": []
+    }"#).build().unwrap();
+
+    let sample = Some(ToString::to_string("samples/liberty"));
+    let overrides = Overrides::new(&fake.root()).unwrap();
+    assert!(overrides.is_some());
+    let sys_interfaces = sys_interfaces(&sample);
+    let config_drive = ConfigDrive::new(&sample).unwrap();
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive.clone(), overrides, &sample, &Some(ToString::to_string("debian"))).unwrap();
+
+    match live_networks.source(&ToString::to_string("eth0")) {
+        Some(&Source::Overrides(ref id)) => assert_eq!(id, "override0"),
+        other => panic!("eth0 is {:?}", other),
+    }
+    // explain names the network eth0 was actually configured from
+    let report = Report::new(&sys_interfaces, &config_drive, &live_networks);
+    let eth0 = report.interfaces.iter().find(|iface| iface.iface == "eth0").unwrap();
+    assert_eq!(eth0.network, Some(ToString::to_string("override0")));
+    assert!(report.to_string().contains("    network: override0\n"));
+    match live_networks.source(&ToString::to_string("eth1")) {
+        Some(&Source::ConfigDrive(_)) => {},
+        other => panic!("eth1 is {:?}", other),
+    }
+
+    let output = live_networks.get_output().unwrap();
+    // Nothing for the link that matched no interface
+    assert_eq!(output.files().len(), 2);
+    let eth0 = output.files().iter().find(|file| file.path.ends_with("eth0")).unwrap();
+    assert!(eth0.content.contains("address 192.0.2.10"));
+    assert!(eth0.content.contains("route add 198.51.100.0/24 via 192.0.2.1"));
+    assert!(!eth0.content.contains("23.253.229.154"));
+    assert!(!eth0.content.contains("gateway 23.253.229.1"));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn only_untouched_glean_files_are_rewritten() {
+    use glean::live_networks::Action;
+    use glean::testing::FakeRoot;
+
+    let fake = FakeRoot::new().dir("etc/sysconfig/network-scripts").build().unwrap();
+    let scripts = fake.path().join("etc/sysconfig/network-scripts");
+    let sample = Some(ToString::to_string("samples/liberty"));
+    let sys_interfaces = sys_interfaces(&sample);
+    let output = || {
+        let config_drive = ConfigDrive::new(&sample).unwrap();
+        let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &fake.root(), &Some(ToString::to_string("redhat"))).unwrap();
+        live_networks.get_output().unwrap()
+    };
+    let action = |iface: &str| {
+        let files = output();
+        let file = files.files().iter().find(|file| file.path.ends_with(format!("ifcfg-{}", iface))).unwrap();
+        file.action.clone()
+    };
+
+    let first = output();
+    assert!(first.files().iter().all(|file| file.action == Action::Create));
+    first.write().unwrap();
+    let generated = first.files()[0].content.clone();
+    assert!(generated.starts_with("# Generated by glean sha256:"));
+    // Nothing has changed since
+    assert_eq!(action("eth0"), Action::Skip);
+    assert_eq!(action("eth1"), Action::Skip);
+
+    // Ours, from before the config changed
+    let eth0 = scripts.join("ifcfg-eth0");
+    fs::write(&eth0, FileToWrite::new(eth0.clone(), String::from("DEVICE=eth0\nBOOTPROTO=dhcp\n")).content).unwrap();
+    assert_eq!(action("eth0"), Action::Replace);
+
+    // Ours, but edited by hand since
+    fs::write(&eth0, format!("{}MTU=9000\n", generated)).unwrap();
+    assert_eq!(action("eth0"), Action::Skip);
+
+    // Never ours
+    fs::write(&eth0, "DEVICE=eth0\nBOOTPROTO=dhcp\n").unwrap();
+    assert_eq!(action("eth0"), Action::Skip);
+
+    // From before glean recorded checksums, regenerated once to get one
+    fs::write(&eth0, "# Generated by glean\nDEVICE=eth0\nBOOTPROTO=dhcp\n").unwrap();
+    assert_eq!(action("eth0"), Action::Replace);
+
+    output().write().unwrap();
+    let mut content = String::new();
+    File::open(&eth0).unwrap().read_to_string(&mut content).unwrap();
+    assert_eq!(content, generated);
+    assert_eq!(action("eth0"), Action::Skip);
+
+    // A file where glean would write is left alone even when nothing
+    // sources it
+    let fake = FakeRoot::new().file("etc/network/interfaces.d/eth0", "auto eth0\niface eth0 inet dhcp\n").build().unwrap();
+    let human = fake.path().join("etc/network/interfaces.d/eth0");
+    let config_drive = ConfigDrive::new(&sample).unwrap();
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &fake.root(), &Some(ToString::to_string("debian"))).unwrap();
+    assert!(!live_networks.config_exists(&ToString::to_string("eth0")));
+    let files = live_networks.get_output().unwrap();
+    assert_eq!(files.files().iter().find(|file| file.path == human).unwrap().action, Action::Skip);
+}
+
+// Needs cargo test --features testing
+#[cfg(feature = "testing")]
+#[test]
+fn fake_roots_go_through_the_whole_pipeline() {
+    use glean::testing::{FakeRoot, Nic};
+    use glean::sys::{Carrier, Reason};
+
+    let mut network_info = String::new();
+    File::open("samples/liberty/mnt/config/openstack/latest/network_info.json").unwrap()
+        .read_to_string(&mut network_info).unwrap();
+    let fake = FakeRoot::new()
+        .nic(Nic::loopback())
+        .nic(Nic::ethernet("eth0", "bc:76:4e:01:62:86").driver("virtio_net").bus("pci"))
+        .nic(Nic::ethernet("eth1", "bc:76:4e:01:62:87").driver("virtio_net").bus("pci"))
+        .nic(Nic::ethernet("eth2", "fa:16:3e:00:00:02").carrier(false))
+        .nic(Nic::ethernet("wlan0", "fa:16:3e:00:00:03").wireless())
+        .bond("bond0", &["eth2"], "active-backup")
+        .vlan("eth0.101", "eth0", 101)
+        .network_info(&network_info)
+        .debian()
+        .build().unwrap();
+    let root = fake.root();
+
+    let mut filter = Filter::new();
+    filter.exclude.push(Rule::from_str("wireless").unwrap());
+    let sys_interfaces = SysInterfaces::new(&root, &None, true, &CarrierProbe::new(Duration::from_secs(0), Strategy::None), &filter).unwrap();
+    let names: Vec<&str> = sys_interfaces.interfaces.iter().map(|iface| &iface.iface[..]).collect();
+    assert!(names.contains(&"eth0") && names.contains(&"eth1"));
+    assert_eq!(names.len(), 2);
+    for rejected in sys_interfaces.rejected.iter() {
+        match (&rejected.iface[..], &rejected.reason) {
+            ("lo", &Reason::Loopback) | ("eth2", &Reason::NoCarrier(Carrier::NotProbed)) | ("wlan0", &Reason::Filtered(_)) => {},
+            ("bond0", &Reason::AddrAssignType(_)) | ("eth0.101", &Reason::AddrAssignType(_)) => {},
+            (iface, reason) => panic!("{} rejected for {}", iface, reason),
+        }
+    }
+    assert_eq!(sys_interfaces.rejected.len(), 5);
+
+    // Debian is detected from the root, and the config drive is used
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &root, &None).unwrap();
+    assert_eq!(live_networks.platform_name(), "debian");
+    let output = live_networks.get_output().unwrap();
+    assert_eq!(output.files().len(), 2);
+    assert!(output.files()[0].content.contains("address 23.253.229.154"));
+
+    // How each interface got carrier is reported
+    assert!(sys_interfaces.interfaces.iter().all(|iface| iface.carrier == Carrier::Up));
+    let report = Report::new(&sys_interfaces, &None, &live_networks);
+    let explained = report.to_string();
+    assert!(explained.contains("eth0\n    address: bc:76:4e:01:62:86\n    accepted: from config drive network0\n    carrier: up\n"));
+    assert!(explained.contains("eth2\n    address: fa:16:3e:00:00:02\n    rejected: no carrier, link not brought up\n"));
+    assert!(Plan::new(&sys_interfaces, &live_networks, &output).to_json().contains("\"carrier\": \"up\""));
+
+    let path = fake.path().clone();
+    drop(fake);
+    assert!(!path.exists());
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn many_interfaces_are_probed_in_sysfs_order() {
+    use glean::testing::{FakeRoot, Nic};
+
+    let mut fake = FakeRoot::new();
+    for index in 0..40 {
+        fake = fake.nic(Nic::ethernet(&format!("eth{:02}", index), &format!("fa:16:3e:00:00:{:02x}", index)));
+    }
+    let fake = fake.build().unwrap();
+    let sys_interfaces = sys_interfaces(&fake.root());
+    let names: Vec<String> = sys_interfaces.interfaces.iter().map(|iface| iface.iface.clone()).collect();
+    let expected: Vec<String> = (0..40).map(|index| format!("eth{:02}", index)).collect();
+    assert_eq!(names, expected);
+}
+
+#[test]
+fn run_plans_without_writing_in_noop() {
+
+    let config = GleanConfig::builder()
+        .root("samples/liberty")
+        .distro("redhat")
+        .carrier(Duration::from_secs(0), Strategy::None)
+        .exclude(Rule::from_str("name:eth1").unwrap())
+        .noop(true)
+        .build();
+    let report = glean::run(&config).unwrap();
+    let network = report.network.unwrap();
+    assert!(!network.written);
+    assert_eq!(network.plan.platform, "redhat");
+    assert_eq!(network.files.files().len(), 1);
+    assert_eq!(network.explanation.interfaces.len(), 2);
+    assert_eq!(report.ran, vec![Task::Network, Task::Hostname, Task::SshKeys]);
+
+    let config = GleanConfig::builder().root("samples/liberty").tasks(&[Task::Hostname]).noop(true).build();
+    let report = glean::run(&config).unwrap();
+    assert!(report.network.is_none());
+    assert_eq!(report.ran, vec![Task::Hostname]);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn run_writes_config_once_per_instance() {
+    use glean::testing::{FakeRoot, Nic};
+
+    let mut network_info = String::new();
+    File::open("samples/liberty/mnt/config/openstack/latest/network_info.json").unwrap()
+        .read_to_string(&mut network_info).unwrap();
+    let fake = FakeRoot::new()
+        .nic(Nic::ethernet("eth0", "bc:76:4e:01:62:86"))
+        .network_info(&network_info)
+        .meta_data("{\"uuid\": \"83679162-1378-4288-a2d4-70e13ec132aa\", \"hostname\": \"test\"}")
+        .redhat()
+        .dir("root")
+        .build().unwrap();
+    let config = GleanConfig::builder()
+        .root(&fake.path().to_string_lossy())
+        .carrier(Duration::from_secs(0), Strategy::None)
+        .build();
+
+    let report = glean::run(&config).unwrap();
+    assert!(report.network.unwrap().written);
+    assert!(fake.path().join("etc/sysconfig/network-scripts/ifcfg-eth0").exists());
+    assert!(fake.path().join("etc/hostname").exists());
+
+    // The same instance booting again has nothing to do
+    let report = glean::run(&config).unwrap();
+    assert!(!report.network.unwrap().written);
+    assert_eq!(report.ran, vec![Task::Network]);
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn only_our_config_for_missing_interfaces_is_removed() {
+    use glean::live_networks::Action;
+    use glean::testing::{FakeRoot, Nic};
+
+    let scripts = PathBuf::from("etc/sysconfig/network-scripts");
+    let ours = FileToWrite::new(scripts.join("ifcfg-eth5"), String::from("DEVICE=eth5\nBOOTPROTO=dhcp\n")).content;
+    let edited = FileToWrite::new(scripts.join("ifcfg-eth7"), String::from("DEVICE=eth7\nBOOTPROTO=dhcp\n")).content + "MTU=9000\n";
+    let fake = FakeRoot::new()
+        .nic(Nic::ethernet("eth0", "bc:76:4e:01:62:86"))
+        .meta_data("{\"uuid\": \"83679162-1378-4288-a2d4-70e13ec132aa\", \"hostname\": \"test\"}")
+        .redhat()
+        .file("etc/sysconfig/network-scripts/ifcfg-eth5", &ours)
+        .file("etc/sysconfig/network-scripts/ifcfg-eth6", "DEVICE=eth6\nBOOTPROTO=dhcp\n")
+        .file("etc/sysconfig/network-scripts/ifcfg-eth7", &edited)
+        .build().unwrap();
+    let scripts = fake.path().join(scripts);
+    let config = |noop: bool| GleanConfig::builder()
+        .root(&fake.path().to_string_lossy())
+        .carrier(Duration::from_secs(0), Strategy::None)
+        .tasks(&[Task::Network])
+        .noop(noop)
+        .build();
+
+    // noop says what would go, and leaves it there
+    let network = glean::run(&config(true)).unwrap().network.unwrap();
+    let removed: Vec<&PathBuf> = network.files.files().iter()
+        .filter(|file| file.action == Action::Remove)
+        .map(|file| &file.path)
+        .collect();
+    assert_eq!(removed, vec![&scripts.join("ifcfg-eth5")]);
+    assert!(scripts.join("ifcfg-eth5").exists());
+
+    assert!(glean::run(&config(false)).unwrap().network.unwrap().written);
+    assert!(!scripts.join("ifcfg-eth5").exists());
+    assert!(scripts.join("ifcfg-eth6").exists());
+    assert!(scripts.join("ifcfg-eth7").exists());
+    assert!(scripts.join("ifcfg-eth0").exists());
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn nothing_is_stale_without_sysfs() {
+    use glean::testing::FakeRoot;
+
+    let ours = FileToWrite::new(PathBuf::from("ifcfg-eth5"), String::from("DEVICE=eth5\nBOOTPROTO=dhcp\n")).content;
+    let fake = FakeRoot::new()
+        .redhat()
+        .file("etc/sysconfig/network-scripts/ifcfg-eth5", &ours)
+        .build().unwrap();
+    let empty = sys_interfaces(&fake.root());
+    assert!(!empty.is_available());
+    let live_networks = LiveNetworks::new(&empty, None, None, &fake.root(), &None).unwrap();
+    assert!(live_networks.get_output().unwrap().files().is_empty());
+
+    fs::remove_dir_all(fake.path().join("sys")).unwrap();
+    let missing = sys_interfaces(&fake.root());
+    let live_networks = LiveNetworks::new(&missing, None, None, &fake.root(), &None).unwrap();
+    assert!(live_networks.get_output().unwrap().files().is_empty());
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn state_tells_reboots_from_new_instances() {
+    use glean::state::State;
+    use glean::testing::FakeRoot;
+
+    let fake = FakeRoot::new().build().unwrap();
+    let state_root = fake.root();
+    let sample = Some(ToString::to_string("samples/liberty"));
+    let scan = |exclude: &str| {
+        let mut filter = Filter::new();
+        if !exclude.is_empty() {
+            filter.exclude.push(Rule::from_str(exclude).unwrap());
+        }
+        SysInterfaces::new(&sample, &None, true, &CarrierProbe::new(Duration::from_secs(0), Strategy::None), &filter).unwrap()
+    };
+    let both = scan("");
+    let only_eth0 = scan("name:eth1");
+    let uuid = Some(ToString::to_string("83679162-1378-4288-a2d4-70e13ec132aa"));
+    let other = Some(ToString::to_string("5c2f7aa4-3e5b-4f8c-9d61-0a4e8d3b2c11"));
+
+    assert!(State::load(&state_root).unwrap().is_none());
+    let live_networks = LiveNetworks::new(&both, ConfigDrive::new(&sample).unwrap(), None, &sample, &Some(ToString::to_string("redhat"))).unwrap();
+    let files = live_networks.get_output().unwrap();
+    let saved = State::new(&None, &uuid, &both, &live_networks, &files);
+    saved.save(&state_root).unwrap();
+
+    let state = State::load(&state_root).unwrap().unwrap();
+    assert_eq!(state.to_json(), saved.to_json());
+    assert_eq!(state.instance_uuid, uuid);
+    assert_eq!(state.tasks, vec!["network"]);
+    assert_eq!(state.platform, "redhat");
+    let names: Vec<&str> = state.interfaces.iter().map(|iface| &iface.name[..]).collect();
+    assert_eq!(names, vec!["eth0", "eth1"]);
+    assert_eq!(state.files.len(), 3);
+
+    assert!(state.is_reboot(&uuid, &both));
+    assert!(!state.is_reboot(&other, &both));
+    assert!(!state.is_reboot(&None, &both));
+    // A NIC gone, or one more than last time
+    assert!(!state.is_reboot(&uuid, &only_eth0));
+    let live_networks = LiveNetworks::new(&only_eth0, ConfigDrive::new(&sample).unwrap(), None, &sample, &Some(ToString::to_string("redhat"))).unwrap();
+    let fewer = State::new(&None, &uuid, &only_eth0, &live_networks, &live_networks.get_output().unwrap());
+    assert!(fewer.is_reboot(&uuid, &only_eth0));
+    assert!(!fewer.is_reboot(&uuid, &both));
+
+    // Per-instance tasks don't make it a reboot until network config is
+    // written for the instance too
+    let mut state = State::empty();
+    state.record(&uuid, &[Task::Hostname]);
+    assert!(!state.is_reboot(&uuid, &both));
+}
+
+#[test]
+fn settings_are_defaults_under_the_command_line() {
+
+    let settings = Settings::parse("
+# per image policy
+[glean]
+distro = debian   ; not redhat
+ssh-keys = no
+
+[network]
+exclude = name:docker* bus:usb
+exclude = wireless
+carrier-timeout = 3
+dns = off
+").unwrap();
+    assert_eq!(settings.exclude.len(), 3);
+
+    let config = settings.apply(GleanConfig::builder()).build();
+    assert_eq!(config.distro, Some(ToString::to_string("debian")));
+    assert_eq!(config.tasks, vec![Task::Network, Task::Hostname]);
+    assert_eq!(config.probe.timeout, Duration::from_secs(3));
+    assert_eq!(config.probe.strategy, Strategy::Events);
+    assert!(!config.dns);
+
+    let config = settings.apply(GleanConfig::builder()).distro("redhat").filter(Filter::new()).build();
+    assert_eq!(config.distro, Some(ToString::to_string("redhat")));
+    assert!(config.filter.exclude.is_empty());
+
+    // Unknown keys are skipped, bad values are not
+    assert!(Settings::parse("[glean]\ncolour = blue\n").is_ok());
+    assert!(Settings::parse("[network]\nexclude = name:eth0 nonsense\n").is_err());
+    assert!(Settings::parse("[glean]\nhostname = maybe\n").is_err());
+    assert!(Settings::new(&Some(ToString::to_string("samples/liberty"))).unwrap().distro.is_none());
+}
+
+#[test]
+fn config_drive_dns_can_be_left_out() {
+
+    let config = GleanConfig::builder()
+        .root("samples/liberty")
+        .distro("debian")
+        .carrier(Duration::from_secs(0), Strategy::None)
+        .tasks(&[Task::Network])
+        .dns(false)
+        .noop(true)
+        .build();
+    let network = glean::run(&config).unwrap().network.unwrap();
+    for file in network.files.files().iter() {
+        assert!(!file.content.contains("dns-nameservers"));
+    }
+}
+
+#[test]
+fn kernel_cmdline_understands_dracut_ip() {
+
+    let cmdline = KernelCmdline::parse("BOOT_IMAGE=/vmlinuz ro quiet=\"a b\" glean.noop glean.distro=debian glean.bogus \
+        ip=10.0.0.5::10.0.0.1:255.255.255.0:host:eth1:none:1400:fa:16:3e:00:00:01 \
+        ip=[2001:db8::5]::[2001:db8::1]:64::eth2:off:[2001:db8::53] \
+        ip=eth3:dhcp ip=eth4:carrier-pigeon nameserver=8.8.8.8");
+    assert!(cmdline.noop && !cmdline.disable && !cmdline.dhcp);
+    assert_eq!(cmdline.distro, Some(ToString::to_string("debian")));
+    assert_eq!(cmdline.devices.len(), 3);
+
+    let eth1 = &cmdline.devices[0];
+    assert_eq!(eth1.name, "eth1");
+    assert_eq!(eth1.method, Method::Static);
+    assert_eq!(eth1.addresses[0].to_string(), "10.0.0.5/24");
+    assert_eq!(eth1.routes[0].gateway.to_string(), "10.0.0.1");
+    assert!(eth1.routes[0].destination.is_default());
+    assert_eq!(eth1.mtu, Some(1400));
+    assert_eq!(eth1.hardware_address.as_ref().unwrap().to_string(), "fa:16:3e:00:00:01");
+    assert_eq!(eth1.dns, vec!["8.8.8.8".parse::<IpAddr>().unwrap()]);
+
+    let eth2 = &cmdline.devices[1];
+    assert_eq!(eth2.addresses[0].to_string(), "2001:db8::5/64");
+    assert_eq!(eth2.dns, vec!["2001:db8::53".parse::<IpAddr>().unwrap()]);
+    assert_eq!(cmdline.devices[2].method, Method::Dhcp);
+
+    assert!(KernelCmdline::parse("ip=dhcp").dhcp);
+    assert!(!KernelCmdline::parse("glean.dhcp=0").dhcp);
+    assert!(KernelCmdline::parse("glean.disable").disable);
+}
+
+#[test]
+fn kernel_cmdline_wins_over_config_drive() {
+
+    let base = || GleanConfig::builder()
+        .root("samples/liberty")
+        .carrier(Duration::from_secs(0), Strategy::None)
+        .tasks(&[Task::Network])
+        .exclude(Rule::from_str("name:eth0").unwrap());
+
+    let cmdline = KernelCmdline::parse("glean.distro=debian glean.interfaces=eth0,eth1 ip=10.0.0.5::10.0.0.1:24::eth1:none glean.noop");
+    let network = glean::run(&cmdline.apply(base()).build()).unwrap().network.unwrap();
+    assert!(!network.written);
+    assert_eq!(network.plan.platform, "debian");
+    // The allow-list replaces include rules, exclude rules still win
+    assert_eq!(network.files.files().len(), 1);
+    let content = &network.files.files()[0].content;
+    assert!(content.contains("address 10.0.0.5") && content.contains("gateway 10.0.0.1"));
+    for iface in network.explanation.interfaces.iter().filter(|iface| iface.iface == "eth1") {
+        match iface.status {
+            Status::Accepted(Source::KernelCmdline(ref id)) => assert_eq!(id, "ip=eth1"),
+            _ => assert!(false),
+        }
+    }
+
+    let cmdline = KernelCmdline::parse("glean.dhcp glean.noop glean.distro=redhat");
+    let network = glean::run(&cmdline.apply(base()).build()).unwrap().network.unwrap();
+    assert!(network.files.files()[0].content.contains("BOOTPROTO=dhcp"));
+
+    let report = glean::run(&KernelCmdline::parse("glean.disable").apply(base()).build()).unwrap();
+    assert!(report.network.is_none() && report.ran.is_empty());
+}
+
+#[test]
+fn kernel_cmdline_dhcp_is_planned_as_dhcp() {
+
+    let config = KernelCmdline::parse("glean.distro=debian glean.noop ip=eth0:dhcp").apply(GleanConfig::builder()
+        .root("samples/liberty")
+        .carrier(Duration::from_secs(0), Strategy::None)
+        .tasks(&[Task::Network]))
+        .build();
+    let network = glean::run(&config).unwrap().network.unwrap();
+    let eth0 = network.plan.interfaces.iter().find(|iface| iface.name == "eth0").unwrap();
+    assert_eq!(eth0.mode, "dhcp");
+    assert_eq!(eth0.source, Some(ToString::to_string("kernel_cmdline")));
+    assert_eq!(eth0.network, Some(ToString::to_string("ip=eth0")));
+    // eth1 is still config drive's
+    let eth1 = network.plan.interfaces.iter().find(|iface| iface.name == "eth1").unwrap();
+    assert_eq!(eth1.mode, "static");
+    assert!(network.explanation.to_string().contains("accepted: from kernel command line ip=eth0\n"));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn network_runs_dont_need_meta_data() {
+    use glean::testing::{FakeRoot, Nic};
+
+    let mut network_info = String::new();
+    File::open("samples/liberty/mnt/config/openstack/latest/network_info.json").unwrap()
+        .read_to_string(&mut network_info).unwrap();
+    let fake = FakeRoot::new()
+        .nic(Nic::ethernet("eth0", "bc:76:4e:01:62:86"))
+        .network_info(&network_info)
+        .meta_data("{ not json")
+        .debian()
+        .build().unwrap();
+    let config = |tasks: &[Task]| GleanConfig::builder()
+        .root(&fake.root().unwrap())
+        .carrier(Duration::from_secs(0), Strategy::None)
+        .tasks(tasks)
+        .build();
+
+    let report = glean::run(&config(&[Task::Network])).unwrap();
+    assert!(report.network.unwrap().written);
+    assert!(fake.path().join("etc/network/interfaces.d/eth0").exists());
+    match glean::run(&config(&[Task::Network, Task::Hostname])) {
+        Err(glean::Error::Decode(path, _)) => assert!(path.ends_with("meta_data.json")),
+        other => panic!("got {:?}", other.map(|report| report.ran)),
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn per_instance_tasks_are_remembered_separately() {
+    use glean::state::State;
+    use glean::testing::FakeRoot;
+
+    let fake = FakeRoot::new()
+        .meta_data("{\"uuid\": \"83679162-1378-4288-a2d4-70e13ec132aa\", \"hostname\": \"test\", \"public_keys\": {\"mykey\": \"ssh-rsa AAAA test\"}}")
+        .dir("etc")
+        .dir("root")
+        .build().unwrap();
+    let only = |tasks: &[Task]| GleanConfig::builder().root(&fake.path().to_string_lossy()).tasks(tasks).build();
+
+    assert_eq!(glean::run(&only(&[Task::Hostname])).unwrap().ran, vec![Task::Hostname]);
+    assert!(glean::run(&only(&[Task::Hostname])).unwrap().ran.is_empty());
+    // ssh keys are still due for this instance
+    assert_eq!(glean::run(&only(&[Task::Hostname, Task::SshKeys])).unwrap().ran, vec![Task::SshKeys]);
+    assert!(glean::run(&only(&[Task::Hostname, Task::SshKeys])).unwrap().ran.is_empty());
+
+    // The one record of what was done for the instance is the state file
+    let state = State::load(&fake.root()).unwrap().unwrap();
+    assert_eq!(state.instance_uuid, Some(ToString::to_string("83679162-1378-4288-a2d4-70e13ec132aa")));
+    assert_eq!(state.tasks, vec!["hostname", "ssh-keys"]);
+    assert!(!fake.path().join("var/lib/glean/instance-id").exists());
+
+    // A new instance starts over
+    fs::write(fake.path().join("mnt/config/openstack/latest/meta_data.json"),
+              "{\"uuid\": \"5c2f7aa4-3e5b-4f8c-9d61-0a4e8d3b2c11\", \"hostname\": \"test\"}").unwrap();
+    assert_eq!(glean::run(&only(&[Task::Hostname])).unwrap().ran, vec![Task::Hostname]);
+}
+
+// Renders a sample for a platform and compares each file with the one in
+// tests/golden/<platform>/<sample>, which have to match one for one. With
+// GLEAN_REGENERATE_GOLDEN set, the golden files are rewritten instead.
+fn check_golden(sample: &str, platform: &str) {
+    let root = Some(format!("samples/{}", sample));
+    let config_drive = ConfigDrive::new(&root).unwrap();
+    let sys_interfaces = sys_interfaces(&root);
+    let live_networks = LiveNetworks::new(&sys_interfaces, config_drive, None, &root, &Some(ToString::to_string(platform))).unwrap();
+    let output = live_networks.get_output().unwrap();
+    let golden = PathBuf::from("tests/golden").join(platform).join(sample);
+
+    if std::env::var_os("GLEAN_REGENERATE_GOLDEN").is_some() {
+        if golden.exists() {
+            fs::remove_dir_all(&golden).unwrap();
+        }
+        fs::create_dir_all(&golden).unwrap();
+        for file in output.files().iter() {
+            fs::write(golden.join(file.path.file_name().unwrap()), &file.content).unwrap();
+        }
+        return;
+    }
+
+    let mut expected: Vec<String> = fs::read_dir(&golden).unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    expected.sort();
+    let mut rendered: Vec<String> = output.files().iter()
+        .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    rendered.sort();
+    assert_eq!(rendered, expected, "files for {} on {}", sample, platform);
+
+    for file in output.files().iter() {
+        let name = file.path.file_name().unwrap();
+        let mut content = String::new();
+        File::open(golden.join(name)).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(file.content, content, "{} for {} on {}", name.to_string_lossy(), sample, platform);
+    }
+}
+
+// Every tree under samples/ on every built in platform, so a new sample or
+// platform is covered as soon as it has golden files
+#[test]
+fn samples_match_golden_files() {
+
+    let mut samples: Vec<String> = fs::read_dir("samples").unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    samples.sort();
+    for platform in Registry::builtin().names() {
+        for sample in samples.iter() {
+            check_golden(sample, &platform);
+        }
+    }
+}
+
+#[test]
+fn invalid_names_are_not_written() {
+
+    let root = Some(ToString::to_string("samples/bond"));
+    let mut config_drive = ConfigDrive::new(&root).unwrap().unwrap();
+    for device in config_drive.network.devices.iter_mut() {
+        if device.id == "bond0" {
+            device.name = String::from("bond0\nup rm -rf /");
+        }
+    }
+    let sys_interfaces = sys_interfaces(&root);
+    let live_networks = LiveNetworks::new(&sys_interfaces, Some(config_drive), None, &root, &Some(ToString::to_string("debian"))).unwrap();
+    match live_networks.get_output() {
+        Err(glean::Error::InvalidConfig(_, _)) => {},
+        _ => assert!(false),
     }
 }